@@ -0,0 +1,219 @@
+use parse_int::parse;
+use std::collections::BTreeSet;
+use std::time::{Duration, Instant};
+
+use ross_protocol::event::bootloader::BootloaderHelloEvent;
+
+use crate::cancellation::CancellationToken;
+use crate::ross_configurator::*;
+
+pub struct DeviceResult {
+    pub address: u16,
+    pub duration: Duration,
+    pub result: Result<(), ConfiguratorError>,
+}
+
+pub fn parse_addresses(values: &[&str]) -> Result<Vec<u16>, ConfiguratorError> {
+    let mut addresses = vec![];
+    let mut seen = BTreeSet::new();
+
+    for value in values.iter() {
+        let (first, last) = match value.split_once('-') {
+            Some((first, last)) => (parse::<u16>(first.trim()), parse::<u16>(last.trim())),
+            None => (parse::<u16>(value), parse::<u16>(value)),
+        };
+
+        let (first, last) = match (first, last) {
+            (Ok(first), Ok(last)) if first <= last => (first, last),
+            _ => {
+                eprintln!("ADDRESS is not a number or an address range.");
+                return Err(ConfiguratorError::BadUsage);
+            }
+        };
+
+        for address in first..=last {
+            if seen.insert(address) {
+                addresses.push(address);
+            }
+        }
+    }
+
+    Ok(addresses)
+}
+
+pub fn all_addresses(devices: &BTreeSet<BootloaderHelloEvent>) -> Vec<u16> {
+    devices
+        .iter()
+        .map(|device| device.bootloader_address)
+        .collect()
+}
+
+pub fn run_for_each<F>(
+    addresses: &[u16],
+    cancellation: &CancellationToken,
+    mut operation: F,
+) -> Vec<DeviceResult>
+where
+    F: FnMut(u16) -> Result<(), ConfiguratorError>,
+{
+    let mut results = vec![];
+
+    for (i, address) in addresses.iter().enumerate() {
+        if cancellation.is_cancelled() {
+            eprintln!(
+                "Skipping {} remaining device(s) after cancellation.",
+                addresses.len() - i
+            );
+
+            for address in addresses[i..].iter() {
+                results.push(DeviceResult {
+                    address: *address,
+                    duration: Duration::from_secs(0),
+                    result: Err(ConfiguratorError::SkippedAfterCancellation),
+                });
+            }
+
+            break;
+        }
+
+        results.push(run_device(
+            (i + 1, addresses.len()),
+            *address,
+            &mut operation,
+        ));
+    }
+
+    results
+}
+
+pub fn run_device<F>((number, count): (usize, usize), address: u16, operation: F) -> DeviceResult
+where
+    F: FnOnce(u16) -> Result<(), ConfiguratorError>,
+{
+    println!(
+        "Processing device {} of {} (address: {:#06x}).",
+        number, count, address
+    );
+
+    let start = Instant::now();
+    let result = operation(address);
+
+    if let Err(ref err) = result {
+        eprintln!("Device (address: {:#06x}) failed: {:?}", address, err);
+    }
+
+    DeviceResult {
+        address,
+        duration: start.elapsed(),
+        result,
+    }
+}
+
+pub fn print_summary(results: &[DeviceResult]) {
+    println!();
+    println!("ADDRESS   RESULT     DURATION  ERROR");
+
+    for result in results.iter() {
+        let (status, error) = match &result.result {
+            Ok(()) => ("ok", String::new()),
+            Err(_) if is_skipped(result) => ("skipped", String::new()),
+            Err(err) => ("failed", format!("{:?}", err)),
+        };
+
+        let row = format!(
+            "{:<9} {:<8} {:>9.1}s  {}",
+            format!("{:#06x}", result.address),
+            status,
+            result.duration.as_secs_f64(),
+            error
+        );
+
+        println!("{}", row.trim_end());
+    }
+
+    let skipped_count = results.iter().filter(|result| is_skipped(result)).count();
+    let failure_count = results
+        .iter()
+        .filter(|result| result.result.is_err())
+        .count()
+        - skipped_count;
+
+    if skipped_count > 0 {
+        println!(
+            "{} succeeded, {} failed, {} skipped.",
+            results.len() - failure_count - skipped_count,
+            failure_count,
+            skipped_count
+        );
+    } else {
+        println!(
+            "{} succeeded, {} failed.",
+            results.len() - failure_count,
+            failure_count
+        );
+    }
+}
+
+fn is_skipped(result: &DeviceResult) -> bool {
+    matches!(
+        result.result,
+        Err(ConfiguratorError::RolloutHalted) | Err(ConfiguratorError::SkippedAfterCancellation)
+    )
+}
+
+pub fn into_result(results: Vec<DeviceResult>) -> Result<(), ConfiguratorError> {
+    for result in results.into_iter() {
+        result.result?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn addresses_and_ranges_are_expanded_in_order() {
+        assert_eq!(
+            parse_addresses(&["0x0012", "0x0010-0x0011", "5"]).unwrap(),
+            vec![0x0012, 0x0010, 0x0011, 0x0005]
+        );
+        assert_eq!(parse_addresses(&["0x10 - 0x10"]).unwrap(), vec![0x0010]);
+    }
+
+    #[test]
+    fn duplicate_addresses_are_kept_once() {
+        assert_eq!(
+            parse_addresses(&["0x0011", "0x0010-0x0012", "0x0011"]).unwrap(),
+            vec![0x0011, 0x0010, 0x0012]
+        );
+    }
+
+    #[test]
+    fn invalid_addresses_are_rejected() {
+        assert!(parse_addresses(&["device"]).is_err());
+        assert!(parse_addresses(&["0x0012-0x0010"]).is_err());
+        assert!(parse_addresses(&["0x10000"]).is_err());
+        assert!(parse_addresses(&["0x0010-"]).is_err());
+    }
+
+    #[test]
+    fn devices_after_cancellation_are_skipped() {
+        let cancellation = CancellationToken::new();
+        let mut processed = vec![];
+
+        let results = run_for_each(&[0x0010, 0x0011, 0x0012], &cancellation, |address| {
+            processed.push(address);
+            cancellation.cancel();
+
+            Err(ConfiguratorError::Cancelled { address, offset: 4 })
+        });
+
+        assert_eq!(processed, vec![0x0010]);
+        assert_eq!(results.len(), 3);
+        assert!(!is_skipped(&results[0]));
+        assert!(is_skipped(&results[1]));
+        assert!(is_skipped(&results[2]));
+    }
+}
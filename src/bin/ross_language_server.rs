@@ -0,0 +1,6 @@
+use ross_configurator::language_server::LanguageServer;
+use ross_configurator::ross_configurator::*;
+
+fn main() -> Result<(), ConfiguratorError> {
+    LanguageServer::new().run()
+}
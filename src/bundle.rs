@@ -0,0 +1,151 @@
+use semver::Version;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::firmware::{
+    encode_header, Firmware, FirmwareManifest, FirmwareMetadata, FIRMWARE_MANIFEST_EXTENSION,
+};
+use crate::firmware_repository::check_board_name;
+use crate::ross_configurator::*;
+
+pub fn bundle(
+    firmware_path: &Path,
+    output_path: &Path,
+    board: Option<&str>,
+    version: Option<&str>,
+) -> Result<PathBuf, ConfiguratorError> {
+    let firmware = Firmware::load(firmware_path)?;
+    let metadata = bundle_metadata(firmware.metadata, board, version)?;
+
+    check_board_name(&metadata.board, ConfiguratorError::InvalidFirmwareMetadata)?;
+
+    let name = format!("{}-{}", metadata.board, metadata.version);
+    let image_name = format!("{}.bin", name);
+    let image_path = output_path.join(&image_name);
+    let manifest_path = output_path.join(format!("{}.{}", name, FIRMWARE_MANIFEST_EXTENSION));
+    let manifest = toml::to_string(&FirmwareManifest {
+        image: image_name.clone(),
+        board: metadata.board.clone(),
+        version: metadata.version.clone(),
+    })
+    .map_err(ConfiguratorError::TomlSerializeError)?;
+
+    let mut image = encode_header(&metadata).unwrap_or_default();
+    image.extend_from_slice(&firmware.data);
+
+    fs::create_dir_all(output_path).map_err(ConfiguratorError::IOError)?;
+
+    let source_path = firmware_path
+        .canonicalize()
+        .map_err(ConfiguratorError::IOError)?;
+
+    for path in [&image_path, &manifest_path].iter() {
+        if path.canonicalize().ok().as_ref() == Some(&source_path) {
+            eprintln!(
+                "{} would overwrite the firmware being bundled, choose another OUTPUT.",
+                path.display()
+            );
+            return Err(ConfiguratorError::BadUsage);
+        }
+    }
+
+    fs::write(&image_path, &image).map_err(ConfiguratorError::IOError)?;
+    fs::write(&manifest_path, manifest).map_err(ConfiguratorError::IOError)?;
+
+    println!(
+        "Bundled firmware {} {} ({} bytes) to {}.",
+        metadata.board,
+        metadata.version,
+        firmware.data.len(),
+        manifest_path.display()
+    );
+
+    Ok(manifest_path)
+}
+
+fn bundle_metadata(
+    header_metadata: Option<FirmwareMetadata>,
+    board: Option<&str>,
+    version: Option<&str>,
+) -> Result<FirmwareMetadata, ConfiguratorError> {
+    let version = match version {
+        Some(version) => match Version::parse(version) {
+            Ok(version) => Some(version),
+            Err(_) => {
+                eprintln!("VERSION is not a semantic version.");
+                return Err(ConfiguratorError::BadUsage);
+            }
+        },
+        None => None,
+    };
+
+    match (header_metadata, board, version) {
+        (Some(metadata), board, version) => {
+            let board_matches = board.is_none_or(|board| board == metadata.board);
+            let version_matches = version.is_none_or(|version| version == metadata.version);
+
+            if !board_matches || !version_matches {
+                eprintln!(
+                    "BOARD and VERSION must match the image header ({} {}).",
+                    metadata.board, metadata.version
+                );
+                return Err(ConfiguratorError::InvalidFirmwareMetadata);
+            }
+
+            Ok(metadata)
+        }
+        (None, Some(board), Some(version)) => Ok(FirmwareMetadata {
+            board: board.to_string(),
+            version,
+        }),
+        (None, _, _) => {
+            eprintln!("Firmware has no version metadata, specify it with --board and --version.");
+            Err(ConfiguratorError::BadUsage)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(board: &str, version: &str) -> FirmwareMetadata {
+        FirmwareMetadata {
+            board: board.to_string(),
+            version: Version::parse(version).unwrap(),
+        }
+    }
+
+    #[test]
+    fn header_metadata_is_used_when_arguments_are_omitted_or_match() {
+        assert_eq!(
+            bundle_metadata(Some(metadata("relay", "1.4.2")), None, None).unwrap(),
+            metadata("relay", "1.4.2")
+        );
+        assert_eq!(
+            bundle_metadata(
+                Some(metadata("relay", "1.4.2")),
+                Some("relay"),
+                Some("1.4.2")
+            )
+            .unwrap(),
+            metadata("relay", "1.4.2")
+        );
+    }
+
+    #[test]
+    fn arguments_must_not_contradict_the_header() {
+        assert!(bundle_metadata(Some(metadata("relay", "1.4.2")), Some("bcm"), None).is_err());
+        assert!(bundle_metadata(Some(metadata("relay", "1.4.2")), None, Some("1.5.0")).is_err());
+    }
+
+    #[test]
+    fn headerless_images_need_board_and_version() {
+        assert_eq!(
+            bundle_metadata(None, Some("relay"), Some("1.4.2")).unwrap(),
+            metadata("relay", "1.4.2")
+        );
+        assert!(bundle_metadata(None, Some("relay"), None).is_err());
+        assert!(bundle_metadata(None, Some("relay"), Some("1.4")).is_err());
+    }
+}
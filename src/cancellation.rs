@@ -0,0 +1,83 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::ross_configurator::*;
+
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn install_signal_handler() -> Result<Self, ConfiguratorError> {
+        let token = Self::new();
+        let handler_token = token.clone();
+
+        ctrlc::set_handler(move || {
+            if !handler_token.is_cancelled() {
+                eprintln!(
+                    "Cancelling after the current packet exchange, press Ctrl-C again to abort."
+                );
+                handler_token.cancel();
+            } else {
+                std::process::exit(130);
+            }
+        })
+        .map_err(ConfiguratorError::SignalHandlerError)?;
+
+        Ok(token)
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    pub fn sleep(&self, duration: Duration) -> bool {
+        let start = Instant::now();
+
+        while !self.is_cancelled() {
+            let elapsed = start.elapsed();
+
+            if elapsed >= duration {
+                return true;
+            }
+
+            thread::sleep(
+                (duration - elapsed).min(Duration::from_millis(CANCELLATION_POLL_INTERVAL_MS)),
+            );
+        }
+
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sleep_completes_when_not_cancelled() {
+        assert!(CancellationToken::new().sleep(Duration::from_millis(1)));
+    }
+
+    #[test]
+    fn sleep_stops_when_cancelled() {
+        let token = CancellationToken::new();
+        token.cancel();
+
+        let start = Instant::now();
+
+        assert!(!token.sleep(Duration::from_secs(10)));
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,83 @@
+use std::path::Path;
+
+use ross_config::config::Config;
+use ross_config::serializer::ConfigSerializer;
+
+use crate::config_file::load_config;
+use crate::diagnostics::Severity;
+use crate::inventory::Inventory;
+use crate::lint::lint_config;
+use crate::ross_configurator::*;
+use crate::template::{load_parameters, render_configs};
+
+pub fn check(
+    path: &Path,
+    parameters_path: Option<&Path>,
+    env: Option<&str>,
+    deny_warnings: bool,
+) -> Result<(), ConfiguratorError> {
+    let configs: Vec<(Option<u16>, Config)> = match parameters_path {
+        Some(parameters_path) => render_configs(path, &load_parameters(parameters_path)?, env)?
+            .into_iter()
+            .map(|(address, config)| (Some(address), config))
+            .collect(),
+        None => vec![(None, load_config(path, env)?)],
+    };
+    let inventory = Inventory::load(&Inventory::default_path())?;
+
+    let mut error_count = 0;
+    let mut warning_count = 0;
+
+    for (address, config) in configs.iter() {
+        let config_data = ConfigSerializer::serialize(config)
+            .map_err(ConfiguratorError::ConfigSerializerError)?;
+
+        let lints = lint_config(config, &inventory);
+
+        for lint in lints.iter() {
+            eprintln!("{}", lint);
+        }
+
+        let config_error_count = lints
+            .iter()
+            .filter(|lint| lint.severity == Severity::Error)
+            .count();
+
+        error_count += config_error_count;
+        warning_count += lints.len() - config_error_count;
+
+        if config_error_count > 0 {
+            continue;
+        }
+
+        let subject = match address {
+            Some(address) => format!("Config for device (address: {:#06x})", address),
+            None => "Config".to_string(),
+        };
+
+        println!(
+            "{} is valid (peripherals: {}, event_processors: {}, config_size: {:#010x}, warnings: {}).",
+            subject,
+            config.peripherals.len(),
+            config.event_processors.len(),
+            config_data.len(),
+            lints.len()
+        );
+    }
+
+    if error_count > 0 || (deny_warnings && warning_count > 0) {
+        eprintln!(
+            "Config check failed ({} errors, {} warnings{}).",
+            error_count,
+            warning_count,
+            if deny_warnings {
+                ", warnings denied"
+            } else {
+                ""
+            }
+        );
+        return Err(ConfiguratorError::LintFailed);
+    }
+
+    Ok(())
+}
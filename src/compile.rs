@@ -0,0 +1,41 @@
+use std::fs;
+use std::path::Path;
+
+use ross_config::serializer::ConfigSerializer;
+
+use crate::config_file::{checksum_path, is_config_blob, load_config, write_checksum};
+use crate::ross_configurator::*;
+
+pub fn compile(
+    config_path: &Path,
+    output_path: &Path,
+    env: Option<&str>,
+) -> Result<(), ConfiguratorError> {
+    if !is_config_blob(output_path) {
+        eprintln!(
+            "OUTPUT must have the .{} extension so that it is read back as a compiled config blob.",
+            CONFIG_BLOB_EXTENSION
+        );
+        return Err(ConfiguratorError::BadUsage);
+    }
+
+    let config = load_config(config_path, env)?;
+    let config_data =
+        ConfigSerializer::serialize(&config).map_err(ConfiguratorError::ConfigSerializerError)?;
+
+    fs::write(output_path, &config_data).map_err(ConfiguratorError::IOError)?;
+    let checksum = write_checksum(output_path, &config_data)?;
+
+    println!(
+        "Compiled config to {} (config_size: {:#010x}, sha256: {}).",
+        output_path.display(),
+        config_data.len(),
+        checksum
+    );
+    println!(
+        "Wrote checksum to {}.",
+        checksum_path(output_path).display()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,534 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::config_model::{ConfigModel, CreatorModel, EventProcessorModel};
+use crate::inspect::load_config_model;
+use crate::ross_configurator::*;
+
+pub enum Change<T> {
+    Added(T),
+    Removed(T),
+    Changed(T, T),
+}
+
+type IndexedChanges = Vec<(u32, Change<String>)>;
+
+pub struct EventProcessorChange {
+    pub event_processor: EventProcessorModel,
+    pub added_creators: Vec<CreatorModel>,
+    pub removed_creators: Vec<CreatorModel>,
+}
+
+pub struct ConfigDiff {
+    pub peripherals: Vec<(u32, Change<String>)>,
+    pub initial_state: Vec<(u32, Change<String>)>,
+    pub shifted_state: Vec<(u32, u32, String)>,
+    pub added_event_processors: Vec<EventProcessorModel>,
+    pub removed_event_processors: Vec<EventProcessorModel>,
+    pub changed_event_processors: Vec<EventProcessorChange>,
+    pub moved_event_processors: Vec<(usize, usize, EventProcessorModel)>,
+}
+
+impl ConfigDiff {
+    pub fn new(old: &ConfigModel, new: &ConfigModel) -> Self {
+        let peripherals = diff_indexed(
+            old.peripherals
+                .iter()
+                .map(|peripheral| (peripheral.index, peripheral.peripheral.clone()))
+                .collect(),
+            new.peripherals
+                .iter()
+                .map(|peripheral| (peripheral.index, peripheral.peripheral.clone()))
+                .collect(),
+        );
+
+        let (initial_state, shifted_state) = diff_state(
+            old.initial_state
+                .iter()
+                .map(|state| (state.index, state.value.clone()))
+                .collect(),
+            new.initial_state
+                .iter()
+                .map(|state| (state.index, state.value.clone()))
+                .collect(),
+        );
+
+        let mut unmatched_old: Vec<(usize, &EventProcessorModel)> =
+            old.event_processors.iter().enumerate().collect();
+        let mut matched = vec![];
+        let mut added_event_processors = vec![];
+        let mut changed_event_processors = vec![];
+
+        for (new_position, new_event_processor) in new.event_processors.iter().enumerate() {
+            let position = unmatched_old.iter().position(|(_, old_event_processor)| {
+                old_event_processor.matcher == new_event_processor.matcher
+            });
+
+            let (old_position, old_event_processor) = match position {
+                Some(position) => unmatched_old.remove(position),
+                None => {
+                    added_event_processors.push(new_event_processor.clone());
+                    continue;
+                }
+            };
+
+            matched.push((old_position, new_position));
+
+            let added_creators =
+                difference(&new_event_processor.creators, &old_event_processor.creators);
+            let removed_creators =
+                difference(&old_event_processor.creators, &new_event_processor.creators);
+
+            if !added_creators.is_empty() || !removed_creators.is_empty() {
+                changed_event_processors.push(EventProcessorChange {
+                    event_processor: new_event_processor.clone(),
+                    added_creators,
+                    removed_creators,
+                });
+            }
+        }
+
+        // Processors run in order, so a processor that left the longest run of matched
+        // processors still in their old relative order counts as moved.
+        let old_order: Vec<usize> = matched
+            .iter()
+            .map(|(old_position, _)| *old_position)
+            .collect();
+        let mut sorted_order = old_order.clone();
+        sorted_order.sort_unstable();
+
+        let moved_event_processors = align(&sorted_order, &old_order)
+            .into_iter()
+            .filter_map(|step| match step {
+                (None, Some(index)) => {
+                    let (old_position, new_position) = matched[index];
+
+                    Some((
+                        old_position,
+                        new_position,
+                        new.event_processors[new_position].clone(),
+                    ))
+                }
+                _ => None,
+            })
+            .collect();
+
+        Self {
+            peripherals,
+            initial_state,
+            shifted_state,
+            added_event_processors,
+            removed_event_processors: unmatched_old
+                .into_iter()
+                .map(|(_, event_processor)| event_processor.clone())
+                .collect(),
+            changed_event_processors,
+            moved_event_processors,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.peripherals.is_empty()
+            && self.initial_state.is_empty()
+            && self.added_event_processors.is_empty()
+            && self.removed_event_processors.is_empty()
+            && self.changed_event_processors.is_empty()
+            && self.shifted_state.is_empty()
+            && self.moved_event_processors.is_empty()
+    }
+
+    pub fn print(&self) {
+        if self.is_empty() {
+            println!("Configs are equivalent.");
+            return;
+        }
+
+        print_indexed_changes("Peripherals", &self.peripherals);
+        print_indexed_changes("State variables", &self.initial_state);
+
+        if !self.shifted_state.is_empty() {
+            println!("State variables moved to another index:");
+
+            for (old_index, new_index, value) in self.shifted_state.iter() {
+                println!("  > {} -> {}: {}", old_index, new_index, value);
+            }
+        }
+
+        if !self.added_event_processors.is_empty()
+            || !self.removed_event_processors.is_empty()
+            || !self.changed_event_processors.is_empty()
+            || !self.moved_event_processors.is_empty()
+        {
+            println!("Event processors:");
+
+            for event_processor in self.removed_event_processors.iter() {
+                println!("  - match {}", event_processor.matcher);
+
+                for creator in event_processor.creators.iter() {
+                    println!("      fire {}", creator);
+                }
+            }
+
+            for event_processor in self.added_event_processors.iter() {
+                println!("  + match {}", event_processor.matcher);
+
+                for creator in event_processor.creators.iter() {
+                    println!("      fire {}", creator);
+                }
+            }
+
+            for change in self.changed_event_processors.iter() {
+                println!("  ~ match {}", change.event_processor.matcher);
+
+                for creator in change.removed_creators.iter() {
+                    println!("    - fire {}", creator);
+                }
+
+                for creator in change.added_creators.iter() {
+                    println!("    + fire {}", creator);
+                }
+            }
+
+            for (old_position, new_position, event_processor) in self.moved_event_processors.iter()
+            {
+                println!(
+                    "  > match {} (position {} -> {})",
+                    event_processor.matcher, old_position, new_position
+                );
+            }
+        }
+
+        let (peripherals_added, peripherals_removed, peripherals_changed) =
+            count_changes(&self.peripherals);
+        let (state_added, state_removed, state_changed) = count_changes(&self.initial_state);
+
+        let added_count = peripherals_added + state_added + self.added_event_processors.len();
+        let removed_count =
+            peripherals_removed + state_removed + self.removed_event_processors.len();
+        let changed_count =
+            peripherals_changed + state_changed + self.changed_event_processors.len();
+        let moved_count = self.shifted_state.len() + self.moved_event_processors.len();
+
+        println!(
+            "{} added, {} removed, {} changed, {} moved.",
+            added_count, removed_count, changed_count, moved_count
+        );
+    }
+}
+
+pub fn config_diff(
+    old_path: &Path,
+    new_path: &Path,
+    env: Option<&str>,
+    require_checksum: bool,
+) -> Result<(), ConfiguratorError> {
+    let old = load_config_model(old_path, env, require_checksum)?;
+    let new = load_config_model(new_path, env, require_checksum)?;
+
+    ConfigDiff::new(&old, &new).print();
+
+    Ok(())
+}
+
+fn diff_indexed(
+    old: BTreeMap<u32, String>,
+    mut new: BTreeMap<u32, String>,
+) -> Vec<(u32, Change<String>)> {
+    let mut changes = vec![];
+
+    for (index, old_value) in old.into_iter() {
+        match new.remove(&index) {
+            Some(new_value) if new_value == old_value => {}
+            Some(new_value) => changes.push((index, Change::Changed(old_value, new_value))),
+            None => changes.push((index, Change::Removed(old_value))),
+        }
+    }
+
+    for (index, new_value) in new.into_iter() {
+        changes.push((index, Change::Added(new_value)));
+    }
+
+    changes.sort_by_key(|(index, _)| *index);
+
+    changes
+}
+
+// State variables are only addressed by their position, so inserting one shifts every
+// following index. Values are aligned instead, and only the leftovers are reported as
+// edits; aligned values whose index differs are reported as shifted.
+fn diff_state(
+    old: Vec<(u32, String)>,
+    new: Vec<(u32, String)>,
+) -> (IndexedChanges, Vec<(u32, u32, String)>) {
+    let old_values: Vec<&String> = old.iter().map(|(_, value)| value).collect();
+    let new_values: Vec<&String> = new.iter().map(|(_, value)| value).collect();
+
+    let mut changes = vec![];
+    let mut shifted = vec![];
+    let mut removed = vec![];
+    let mut added = vec![];
+
+    let mut steps = align(&old_values, &new_values);
+    steps.push((None, None));
+
+    for step in steps.into_iter() {
+        match step {
+            (Some(old_position), None) => removed.push(&old[old_position]),
+            (None, Some(new_position)) => added.push(&new[new_position]),
+            _ => {
+                let mut added_rest = added.drain(..);
+
+                for (old_index, old_value) in removed.drain(..) {
+                    match added_rest.next() {
+                        Some((new_index, new_value)) => changes.push((
+                            *new_index,
+                            Change::Changed(old_value.clone(), new_value.clone()),
+                        )),
+                        None => changes.push((*old_index, Change::Removed(old_value.clone()))),
+                    }
+                }
+
+                for (new_index, new_value) in added_rest {
+                    changes.push((*new_index, Change::Added(new_value.clone())));
+                }
+
+                if let (Some(old_position), Some(new_position)) = step {
+                    let (old_index, value) = &old[old_position];
+                    let new_index = new[new_position].0;
+
+                    if *old_index != new_index {
+                        shifted.push((*old_index, new_index, value.clone()));
+                    }
+                }
+            }
+        }
+    }
+
+    changes.sort_by_key(|(index, _)| *index);
+
+    (changes, shifted)
+}
+
+// Longest common subsequence of the two slices, as (old position, new position) steps in
+// order; a missing side marks a removed or added item.
+fn align<T: PartialEq>(old: &[T], new: &[T]) -> Vec<(Option<usize>, Option<usize>)> {
+    let mut lengths = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i][j] = if old[i] == new[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut steps = vec![];
+    let (mut i, mut j) = (0, 0);
+
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            steps.push((Some(i), Some(j)));
+            i += 1;
+            j += 1;
+        } else if j == new.len() || (i < old.len() && lengths[i + 1][j] >= lengths[i][j + 1]) {
+            steps.push((Some(i), None));
+            i += 1;
+        } else {
+            steps.push((None, Some(j)));
+            j += 1;
+        }
+    }
+
+    steps
+}
+
+fn difference(creators: &[CreatorModel], other: &[CreatorModel]) -> Vec<CreatorModel> {
+    let mut unmatched: Vec<&CreatorModel> = other.iter().collect();
+    let mut difference = vec![];
+
+    for creator in creators.iter() {
+        match unmatched.iter().position(|other| *other == creator) {
+            Some(position) => {
+                unmatched.remove(position);
+            }
+            None => difference.push(creator.clone()),
+        }
+    }
+
+    difference
+}
+
+fn count_changes(changes: &[(u32, Change<String>)]) -> (usize, usize, usize) {
+    let mut counts = (0, 0, 0);
+
+    for (_, change) in changes.iter() {
+        match change {
+            Change::Added(_) => counts.0 += 1,
+            Change::Removed(_) => counts.1 += 1,
+            Change::Changed(_, _) => counts.2 += 1,
+        }
+    }
+
+    counts
+}
+
+fn print_indexed_changes(title: &str, changes: &[(u32, Change<String>)]) {
+    if changes.is_empty() {
+        return;
+    }
+
+    println!("{}:", title);
+
+    for (index, change) in changes.iter() {
+        match change {
+            Change::Added(value) => println!("  + {}: {}", index, value),
+            Change::Removed(value) => println!("  - {}: {}", index, value),
+            Change::Changed(old_value, new_value) => {
+                println!("  ~ {}: {} -> {}", index, old_value, new_value)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use ross_dsl::Parser;
+
+    fn diff(old: &str, new: &str) -> ConfigDiff {
+        ConfigDiff::new(
+            &ConfigModel::from_config(&Parser::parse(old).unwrap(), 0),
+            &ConfigModel::from_config(&Parser::parse(new).unwrap(), 0),
+        )
+    }
+
+    fn indexes(changes: &[(u32, Change<String>)]) -> Vec<(u32, &'static str)> {
+        changes
+            .iter()
+            .map(|(index, change)| {
+                let kind = match change {
+                    Change::Added(_) => "added",
+                    Change::Removed(_) => "removed",
+                    Change::Changed(_, _) => "changed",
+                };
+
+                (*index, kind)
+            })
+            .collect()
+    }
+
+    const BUTTON: &str = "do {
+            match event 0x0002~u16;
+            match producer 0x0010~u16;
+            fire { PacketExtractor(); PacketProducer(0x0011~u16); }
+        }";
+    const TICK: &str = "do {
+            match tick;
+            match producer 0x0001~u16;
+            fire { PacketExtractor(); PacketProducer(0x0012~u16); }
+        }";
+
+    #[test]
+    fn identical_configs_have_no_differences() {
+        let config = format!("let light = false;\n{}\n{}", BUTTON, TICK);
+
+        assert!(diff(&config, &config).is_empty());
+    }
+
+    #[test]
+    fn reordered_event_processors_are_reported_as_moved() {
+        let diff = diff(
+            &format!("{}\n{}", BUTTON, TICK),
+            &format!("{}\n{}", TICK, BUTTON),
+        );
+
+        assert!(!diff.is_empty());
+        assert!(diff.added_event_processors.is_empty());
+        assert!(diff.removed_event_processors.is_empty());
+        assert!(diff.changed_event_processors.is_empty());
+        assert_eq!(
+            diff.moved_event_processors
+                .iter()
+                .map(|(old_position, new_position, _)| (*old_position, *new_position))
+                .collect::<Vec<_>>(),
+            vec![(0, 1)]
+        );
+    }
+
+    #[test]
+    fn inserted_state_shifts_instead_of_changing_every_index() {
+        let diff = diff(
+            "let a = false; let b = 1~u8; let c = 0~u32;",
+            "let x = 7~u16; let a = false; let b = 1~u8; let c = 0~u32;",
+        );
+
+        assert_eq!(indexes(&diff.initial_state), vec![(0, "added")]);
+        assert_eq!(
+            diff.shifted_state
+                .iter()
+                .map(|(old_index, new_index, _)| (*old_index, *new_index))
+                .collect::<Vec<_>>(),
+            vec![(0, 1), (1, 2), (2, 3)]
+        );
+    }
+
+    #[test]
+    fn initial_state_changes_are_classified() {
+        let changed = diff(
+            "let a = false; let b = 1~u8;",
+            "let a = true; let b = 1~u8; let c = 0~u32;",
+        );
+        let removed = diff("let a = false; let b = 1~u8;", "let a = false;");
+
+        assert_eq!(
+            indexes(&changed.initial_state),
+            vec![(0, "changed"), (2, "added")]
+        );
+        assert_eq!(indexes(&removed.initial_state), vec![(1, "removed")]);
+        assert!(changed.shifted_state.is_empty());
+        assert!(changed.peripherals.is_empty());
+    }
+
+    #[test]
+    fn event_processors_are_matched_by_their_matcher() {
+        let diff = diff(
+            BUTTON,
+            &format!(
+                "do {{
+                    match event 0x0002~u16;
+                    match producer 0x0010~u16;
+                    fire {{ PacketExtractor(); PacketProducer(0x0013~u16); }}
+                }}
+                {}",
+                TICK
+            ),
+        );
+
+        assert_eq!(diff.added_event_processors.len(), 1);
+        assert!(diff.removed_event_processors.is_empty());
+        assert_eq!(diff.changed_event_processors.len(), 1);
+
+        let change = &diff.changed_event_processors[0];
+
+        assert_eq!(
+            change.added_creators[0].to_string(),
+            "PacketExtractor -> PacketProducer { receiver_address: 19 }"
+        );
+        assert_eq!(
+            change.removed_creators[0].to_string(),
+            "PacketExtractor -> PacketProducer { receiver_address: 17 }"
+        );
+    }
+
+    #[test]
+    fn removed_event_processors_are_reported() {
+        let diff = diff(&format!("{}\n{}", BUTTON, TICK), BUTTON);
+
+        assert!(diff.added_event_processors.is_empty());
+        assert_eq!(diff.removed_event_processors.len(), 1);
+        assert!(diff.changed_event_processors.is_empty());
+    }
+}
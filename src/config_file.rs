@@ -0,0 +1,212 @@
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use ross_config::config::Config;
+use ross_config::serializer::ConfigSerializer;
+use ross_dsl::Parser;
+
+use crate::diagnostics::{parse_diagnostics, print_diagnostics};
+use crate::preprocessor::{
+    aliases_path, load_aliases, load_overlay, preprocess, Definitions, PreprocessedSource,
+};
+use crate::ross_configurator::*;
+use crate::transfer::image_hash;
+
+pub fn read_config_source(path: &Path) -> Result<String, ConfiguratorError> {
+    fs::read_to_string(path).map_err(ConfiguratorError::IOError)
+}
+
+pub fn load_config(path: &Path, env: Option<&str>) -> Result<Config, ConfiguratorError> {
+    load_template(path, &BTreeMap::new(), env)
+}
+
+pub fn load_template(
+    path: &Path,
+    parameters: &BTreeMap<String, String>,
+    env: Option<&str>,
+) -> Result<Config, ConfiguratorError> {
+    let definitions = Definitions {
+        aliases: load_aliases(&aliases_path())?,
+        overlay: match env {
+            Some(env) => load_overlay(path, env)?,
+            None => BTreeMap::new(),
+        },
+        parameters: parameters.clone(),
+    };
+    let source = preprocess(path, &definitions)?;
+
+    if !source.diagnostics.is_empty() {
+        print_diagnostics(path, &source.diagnostics);
+        return Err(ConfiguratorError::PreprocessorError);
+    }
+
+    parse_source(path, &source)
+}
+
+pub fn parse_source(path: &Path, source: &PreprocessedSource) -> Result<Config, ConfiguratorError> {
+    Parser::parse(&source.text).map_err(|err| {
+        let diagnostics = parse_diagnostics(source);
+
+        if diagnostics.is_empty() {
+            eprintln!("Parsing failed with error:");
+            eprintln!("{}", err);
+        } else {
+            print_diagnostics(path, &diagnostics);
+        }
+
+        ConfiguratorError::ParserError(err)
+    })
+}
+
+pub fn is_config_blob(path: &Path) -> bool {
+    path.extension().and_then(|extension| extension.to_str()) == Some(CONFIG_BLOB_EXTENSION)
+}
+
+pub fn checksum_path(path: &Path) -> PathBuf {
+    let mut checksum_path = OsString::from(path.as_os_str());
+    checksum_path.push(".");
+    checksum_path.push(CONFIG_CHECKSUM_EXTENSION);
+
+    PathBuf::from(checksum_path)
+}
+
+pub fn write_checksum(path: &Path, data: &[u8]) -> Result<String, ConfiguratorError> {
+    let checksum = image_hash(data);
+    let file_name = path
+        .file_name()
+        .map(|file_name| file_name.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    fs::write(
+        checksum_path(path),
+        format!("{}  {}\n", checksum, file_name),
+    )
+    .map_err(ConfiguratorError::IOError)?;
+
+    Ok(checksum)
+}
+
+pub fn verify_checksum(
+    path: &Path,
+    data: &[u8],
+    require_checksum: bool,
+) -> Result<(), ConfiguratorError> {
+    let expected = match fs::read_to_string(checksum_path(path)) {
+        Ok(string) => string
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_string(),
+        Err(err) if err.kind() == ErrorKind::NotFound && !require_checksum => {
+            eprintln!("Config blob has no checksum file, skipping checksum verification.");
+            return Ok(());
+        }
+        Err(err) if err.kind() == ErrorKind::NotFound => {
+            eprintln!(
+                "Config blob has no checksum file {}, pass --no-checksum to use it anyway.",
+                checksum_path(path).display()
+            );
+            return Err(ConfiguratorError::ChecksumMissing);
+        }
+        Err(err) => return Err(ConfiguratorError::IOError(err)),
+    };
+
+    let checksum = image_hash(data);
+
+    if checksum != expected {
+        eprintln!(
+            "Config blob checksum {} does not match the expected {}.",
+            checksum, expected
+        );
+        return Err(ConfiguratorError::ChecksumMismatch);
+    }
+
+    eprintln!("Config blob checksum verified (sha256: {}).", checksum);
+
+    Ok(())
+}
+
+pub fn load_config_data(
+    path: &Path,
+    env: Option<&str>,
+    require_checksum: bool,
+) -> Result<Vec<u8>, ConfiguratorError> {
+    if !is_config_blob(path) {
+        let config = load_config(path, env)?;
+
+        return ConfigSerializer::serialize(&config)
+            .map_err(ConfiguratorError::ConfigSerializerError);
+    }
+
+    let data = fs::read(path).map_err(ConfiguratorError::IOError)?;
+
+    if env.is_some() {
+        eprintln!(
+            "Environment overlays cannot be applied to compiled config blob {}.",
+            path.display()
+        );
+        return Err(ConfiguratorError::BadUsage);
+    }
+
+    verify_checksum(path, &data, require_checksum)?;
+
+    ConfigSerializer::deserialize(&data).map_err(ConfiguratorError::ConfigSerializerError)?;
+
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+    use std::process;
+
+    fn blob_path(name: &str) -> PathBuf {
+        let directory = env::temp_dir().join(format!(
+            "ross_configurator_config_file_{}_{}",
+            name,
+            process::id()
+        ));
+        fs::create_dir_all(&directory).unwrap();
+
+        directory.join("config.bin")
+    }
+
+    #[test]
+    fn blobs_are_recognized_by_extension() {
+        assert!(is_config_blob(Path::new("build/config.bin")));
+        assert!(!is_config_blob(Path::new("config.ross")));
+        assert!(!is_config_blob(Path::new("config")));
+    }
+
+    #[test]
+    fn missing_checksum_is_an_error_unless_allowed() {
+        let path = blob_path("missing");
+
+        assert!(matches!(
+            verify_checksum(&path, &[1, 2, 3], true),
+            Err(ConfiguratorError::ChecksumMissing)
+        ));
+        assert!(verify_checksum(&path, &[1, 2, 3], false).is_ok());
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn checksum_must_match_the_data() {
+        let path = blob_path("mismatch");
+        write_checksum(&path, &[1, 2, 3]).unwrap();
+
+        assert!(verify_checksum(&path, &[1, 2, 3], true).is_ok());
+        assert!(matches!(
+            verify_checksum(&path, &[1, 2, 4], false),
+            Err(ConfiguratorError::ChecksumMismatch)
+        ));
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
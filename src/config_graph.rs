@@ -0,0 +1,595 @@
+use std::convert::TryFrom;
+use std::path::Path;
+
+use ross_config::config::Config;
+use ross_config::creator::Creator;
+use ross_config::extractor::*;
+use ross_config::filter::*;
+use ross_config::matcher::Matcher;
+use ross_config::producer::*;
+use ross_config::serializer::{ConfigSerializer, Serialize};
+
+use crate::config_file::load_config_data;
+use crate::config_model::ComponentModel;
+use crate::event_type::EventType;
+use crate::inventory::Inventory;
+use crate::lint::{read_u32, target_address};
+use crate::ross_configurator::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GraphFormat {
+    Dot,
+    Mermaid,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum NodeKind {
+    Input,
+    Processor,
+    State,
+    Output,
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    id: String,
+    kind: NodeKind,
+    label: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Edge {
+    from: usize,
+    to: usize,
+    label: Option<String>,
+    dashed: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct InputEvent {
+    event_code: Option<u16>,
+    message_code: Option<u16>,
+    producer_address: Option<u16>,
+    button_index: Option<u8>,
+}
+
+#[derive(Debug, Default)]
+pub struct ConfigGraph {
+    nodes: Vec<Node>,
+    edges: Vec<Edge>,
+}
+
+pub fn config_graph(
+    path: &Path,
+    format: GraphFormat,
+    env: Option<&str>,
+    require_checksum: bool,
+) -> Result<(), ConfiguratorError> {
+    let config_data = load_config_data(path, env, require_checksum)?;
+    let config = ConfigSerializer::deserialize(&config_data)
+        .map_err(ConfiguratorError::ConfigSerializerError)?;
+    let inventory = Inventory::load(&Inventory::default_path())?;
+
+    let graph = ConfigGraph::from_config(&config, &inventory);
+
+    match format {
+        GraphFormat::Dot => print!("{}", graph.to_dot()),
+        GraphFormat::Mermaid => print!("{}", graph.to_mermaid()),
+    }
+
+    Ok(())
+}
+
+impl ConfigGraph {
+    pub fn from_config(config: &Config, inventory: &Inventory) -> Self {
+        let mut graph = Self::default();
+
+        for (i, event_processor) in config.event_processors.iter().enumerate() {
+            let processor = graph.add_node(NodeKind::Processor, format!("#{}", i));
+
+            let mut inputs = input_events(&event_processor.matcher);
+            inputs.dedup();
+
+            for input in inputs.iter() {
+                let node = graph.add_node(NodeKind::Input, input_label(input, inventory));
+                graph.add_edge(node, processor, None, false);
+            }
+
+            graph.add_state_edges(config, &event_processor.matcher, processor);
+
+            for creator in event_processor.creators.iter() {
+                graph.add_creator(config, creator, processor, inventory);
+            }
+        }
+
+        graph
+    }
+
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph config {\n    rankdir=LR;\n");
+
+        for node in self.nodes.iter() {
+            let shape = match node.kind {
+                NodeKind::Input => "ellipse",
+                NodeKind::Processor => "diamond",
+                NodeKind::State => "cylinder",
+                NodeKind::Output => "box",
+            };
+
+            dot += &format!(
+                "    {} [label=\"{}\", shape={}];\n",
+                node.id,
+                escape_dot(&node.label),
+                shape
+            );
+        }
+
+        for edge in self.edges.iter() {
+            let mut attributes = vec![];
+
+            if let Some(label) = &edge.label {
+                attributes.push(format!("label=\"{}\"", escape_dot(label)));
+            }
+
+            if edge.dashed {
+                attributes.push("style=dashed".to_string());
+            }
+
+            dot += &format!(
+                "    {} -> {}",
+                self.nodes[edge.from].id, self.nodes[edge.to].id
+            );
+
+            if !attributes.is_empty() {
+                dot += &format!(" [{}]", attributes.join(", "));
+            }
+
+            dot += ";\n";
+        }
+
+        dot += "}\n";
+        dot
+    }
+
+    pub fn to_mermaid(&self) -> String {
+        let mut mermaid = String::from("flowchart LR\n");
+
+        for node in self.nodes.iter() {
+            let label = escape_mermaid(&node.label);
+
+            let shape = match node.kind {
+                NodeKind::Input => format!("([\"{}\"])", label),
+                NodeKind::Processor => format!("{{\"{}\"}}", label),
+                NodeKind::State => format!("[(\"{}\")]", label),
+                NodeKind::Output => format!("[\"{}\"]", label),
+            };
+
+            mermaid += &format!("    {}{}\n", node.id, shape);
+        }
+
+        for edge in self.edges.iter() {
+            let arrow = if edge.dashed { "-.->" } else { "-->" };
+
+            let label = match &edge.label {
+                Some(label) => format!("|\"{}\"|", escape_mermaid(label)),
+                None => String::new(),
+            };
+
+            mermaid += &format!(
+                "    {} {}{} {}\n",
+                self.nodes[edge.from].id, arrow, label, self.nodes[edge.to].id
+            );
+        }
+
+        mermaid
+    }
+
+    fn add_node(&mut self, kind: NodeKind, label: String) -> usize {
+        if kind != NodeKind::Processor {
+            if let Some(position) = self
+                .nodes
+                .iter()
+                .position(|node| node.kind == kind && node.label == label)
+            {
+                return position;
+            }
+        }
+
+        let prefix = match kind {
+            NodeKind::Input => "input",
+            NodeKind::Processor => "processor",
+            NodeKind::State => "state",
+            NodeKind::Output => "output",
+        };
+        let count = self.nodes.iter().filter(|node| node.kind == kind).count();
+
+        self.nodes.push(Node {
+            id: format!("{}{}", prefix, count),
+            kind,
+            label,
+        });
+
+        self.nodes.len() - 1
+    }
+
+    fn add_edge(&mut self, from: usize, to: usize, label: Option<String>, dashed: bool) {
+        let edge = Edge {
+            from,
+            to,
+            label,
+            dashed,
+        };
+
+        if !self.edges.contains(&edge) {
+            self.edges.push(edge);
+        }
+    }
+
+    fn add_state_node(&mut self, config: &Config, state_index: u32) -> usize {
+        let label = match config.initial_state.get(&state_index) {
+            Some(value) => format!("state {} = {:?}", state_index, value),
+            None => format!("state {}", state_index),
+        };
+
+        self.add_node(NodeKind::State, label)
+    }
+
+    fn add_state_edges(&mut self, config: &Config, matcher: &Matcher, processor: usize) {
+        match matcher {
+            Matcher::Single { filter, .. } => {
+                let code = filter.get_code();
+                let data = filter.serialize();
+                let label = Some(short_name(code, filter.as_ref(), "Filter"));
+
+                match code {
+                    STATE_EQUAL_TO_CONST_FILTER_CODE
+                    | STATE_EQUAL_TO_VALUE_FILTER_CODE
+                    | STATE_MORE_THAN_CONST_FILTER_CODE
+                    | STATE_LESS_THAN_CONST_FILTER_CODE => {
+                        if let Some(state_index) = read_u32(&data, 0) {
+                            let state = self.add_state_node(config, state_index);
+                            self.add_edge(state, processor, label, true);
+                        }
+                    }
+                    STATE_EQUAL_TO_STATE_FILTER_CODE => {
+                        for offset in [0, 4] {
+                            if let Some(state_index) = read_u32(&data, offset) {
+                                let state = self.add_state_node(config, state_index);
+                                self.add_edge(state, processor, label.clone(), true);
+                            }
+                        }
+                    }
+                    INCREMENT_STATE_BY_CONST_FILTER_CODE
+                    | INCREMENT_STATE_BY_VALUE_FILTER_CODE
+                    | DECREMENT_STATE_BY_CONST_FILTER_CODE
+                    | DECREMENT_STATE_BY_VALUE_FILTER_CODE
+                    | SET_STATE_TO_CONST_FILTER_CODE
+                    | SET_STATE_TO_VALUE_FILTER_CODE
+                    | FLIP_STATE_FILTER_CODE => {
+                        if let Some(state_index) = read_u32(&data, 0) {
+                            let state = self.add_state_node(config, state_index);
+                            self.add_edge(processor, state, label, false);
+                        }
+                    }
+                    SET_STATE_TO_STATE_FILTER_CODE => {
+                        if let Some(state_index) = read_u32(&data, 4) {
+                            let state = self.add_state_node(config, state_index);
+                            self.add_edge(state, processor, label.clone(), true);
+                        }
+
+                        if let Some(state_index) = read_u32(&data, 0) {
+                            let state = self.add_state_node(config, state_index);
+                            self.add_edge(processor, state, label, false);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Matcher::Not(matcher) => self.add_state_edges(config, matcher, processor),
+            Matcher::Or(left, right) | Matcher::And(left, right) => {
+                self.add_state_edges(config, left, processor);
+                self.add_state_edges(config, right, processor);
+            }
+        }
+    }
+
+    fn add_creator(
+        &mut self,
+        config: &Config,
+        creator: &Creator,
+        processor: usize,
+        inventory: &Inventory,
+    ) {
+        let code = creator.producer.get_code();
+        let data = creator.producer.serialize();
+
+        let name = short_name(code, creator.producer.as_ref(), "Producer");
+        let mut label = name.clone();
+
+        if let Some(matcher) = &creator.matcher {
+            self.add_state_edges(config, matcher, processor);
+            label += " (conditional)";
+        }
+
+        let output = self.add_node(NodeKind::Output, output_label(code, &data, name, inventory));
+        self.add_edge(processor, output, Some(label), false);
+
+        let state_offset = match code {
+            BCM_CHANGE_BRIGHTNESS_STATE_PRODUCER_CODE => Some(3),
+            BCM_ANIMATE_BRIGHTNESS_STATE_PRODUCER_CODE => Some(7),
+            _ => None,
+        };
+
+        if let Some(state_index) = state_offset.and_then(|offset| read_u32(&data, offset)) {
+            let state = self.add_state_node(config, state_index);
+            self.add_edge(state, output, None, true);
+        }
+    }
+}
+
+fn input_events(matcher: &Matcher) -> Vec<InputEvent> {
+    match matcher {
+        Matcher::Single { extractor, filter } => {
+            let mut input = InputEvent::default();
+
+            if filter.get_code() == VALUE_EQUAL_TO_CONST_FILTER_CODE {
+                let value = const_value(&filter.serialize());
+
+                match extractor.get_code() {
+                    EVENT_CODE_EXTRACTOR_CODE => {
+                        input.event_code = value.and_then(|value| u16::try_from(value).ok())
+                    }
+                    MESSAGE_CODE_EXTRACTOR_CODE => {
+                        input.message_code = value.and_then(|value| u16::try_from(value).ok())
+                    }
+                    EVENT_PRODUCER_ADDRESS_EXTRACTOR_CODE => {
+                        input.producer_address = value.and_then(|value| u16::try_from(value).ok())
+                    }
+                    BUTTON_INDEX_EXTRACTOR_CODE => {
+                        input.button_index = value.and_then(|value| u8::try_from(value).ok())
+                    }
+                    _ => {}
+                }
+            }
+
+            vec![input]
+        }
+        Matcher::Not(_) => vec![InputEvent::default()],
+        Matcher::Or(left, right) => {
+            let mut inputs = input_events(left);
+            inputs.extend(input_events(right));
+            inputs
+        }
+        Matcher::And(left, right) => {
+            let right_inputs = input_events(right);
+            let mut inputs = vec![];
+
+            for left_input in input_events(left).iter() {
+                for right_input in right_inputs.iter() {
+                    inputs.push(InputEvent {
+                        event_code: left_input.event_code.or(right_input.event_code),
+                        message_code: left_input.message_code.or(right_input.message_code),
+                        producer_address: left_input
+                            .producer_address
+                            .or(right_input.producer_address),
+                        button_index: left_input.button_index.or(right_input.button_index),
+                    });
+                }
+            }
+
+            inputs
+        }
+    }
+}
+
+fn const_value(data: &[u8]) -> Option<u32> {
+    match data {
+        [0x00, value] => Some(*value as u32),
+        [0x01, high, low] => Some(u16::from_be_bytes([*high, *low]) as u32),
+        [0x02, ..] => read_u32(data, 1),
+        _ => None,
+    }
+}
+
+fn input_label(input: &InputEvent, inventory: &Inventory) -> String {
+    let mut label = match input.event_code {
+        Some(code) => match EventType::all()
+            .into_iter()
+            .find(|event_type| event_type.code() == code)
+        {
+            Some(event_type) => event_type.to_string(),
+            None => format!("event {:#06x}", code),
+        },
+        None => "any event".to_string(),
+    };
+
+    if let Some(code) = input.message_code {
+        label += &format!(" message {:#06x}", code);
+    }
+
+    if let Some(address) = input.producer_address {
+        label += &format!(" from {}", device_label(address, inventory));
+    }
+
+    if let Some(index) = input.button_index {
+        label += &format!(" button {}", index);
+    }
+
+    label
+}
+
+fn output_label(code: u16, data: &[u8], name: String, inventory: &Inventory) -> String {
+    let device = match target_address(code, data) {
+        Some(address) => device_label(address, inventory),
+        None => return name,
+    };
+
+    match code {
+        PACKET_PRODUCER_CODE => format!("packet to {}", device),
+        MESSAGE_PRODUCER_CODE if data.len() >= 4 => format!(
+            "message {:#06x} to {}",
+            u16::from_be_bytes([data[2], data[3]]),
+            device
+        ),
+        RELAY_SET_VALUE_PRODUCER_CODE if data.len() >= 3 => {
+            format!("relay {} on {}", data[2], device)
+        }
+        _ if data.len() >= 3 => format!("BCM channel {} on {}", data[2], device),
+        _ => device,
+    }
+}
+
+fn device_label(address: u16, inventory: &Inventory) -> String {
+    match inventory
+        .device(address)
+        .and_then(|device| device.name.as_ref())
+    {
+        Some(name) => format!("{:#06x} ({})", address, name),
+        None => format!("{:#06x}", address),
+    }
+}
+
+fn short_name<T: std::fmt::Debug + Serialize + ?Sized>(
+    code: u16,
+    component: &T,
+    suffix: &str,
+) -> String {
+    let name = ComponentModel::new(code, component).name;
+
+    match name.strip_suffix(suffix) {
+        Some(name) => name.to_string(),
+        None => name,
+    }
+}
+
+fn escape_dot(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn escape_mermaid(label: &str) -> String {
+    label.replace('#', "#35;").replace('"', "#quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use ross_dsl::Parser;
+
+    const CONFIG: &str = "let light = false;
+        do {
+            match event BUTTON_PRESSED_EVENT_CODE;
+            match producer 0x0011~u16;
+            match { ButtonIndexExtractor(); ValueEqualToConstFilter(2~u8); }
+            match { FlipStateFilter(light); }
+            fire { NoneExtractor(); BcmChangeBrightnessStateProducer(0x0012~u16, 1~u8, light); }
+        }";
+
+    fn graph() -> ConfigGraph {
+        ConfigGraph::from_config(&Parser::parse(CONFIG).unwrap(), &Inventory::default())
+    }
+
+    fn labels(graph: &ConfigGraph, kind: NodeKind) -> Vec<&str> {
+        graph
+            .nodes
+            .iter()
+            .filter(|node| node.kind == kind)
+            .map(|node| node.label.as_str())
+            .collect()
+    }
+
+    fn edge(graph: &ConfigGraph, from: &str, to: &str) -> Option<Edge> {
+        graph
+            .edges
+            .iter()
+            .find(|edge| graph.nodes[edge.from].id == from && graph.nodes[edge.to].id == to)
+            .cloned()
+    }
+
+    #[test]
+    fn nodes_are_extracted_from_the_config() {
+        let graph = graph();
+
+        assert_eq!(
+            labels(&graph, NodeKind::Input),
+            vec!["ButtonPressed from 0x0011 button 2"]
+        );
+        assert_eq!(labels(&graph, NodeKind::Processor), vec!["#0"]);
+        assert_eq!(
+            labels(&graph, NodeKind::State),
+            vec!["state 0 = Bool(false)"]
+        );
+        assert_eq!(
+            labels(&graph, NodeKind::Output),
+            vec!["BCM channel 1 on 0x0012"]
+        );
+    }
+
+    #[test]
+    fn edges_connect_inputs_states_and_outputs() {
+        let graph = graph();
+
+        assert_eq!(graph.edges.len(), 4);
+        assert!(edge(&graph, "input0", "processor0").is_some());
+        assert_eq!(
+            edge(&graph, "processor0", "state0")
+                .unwrap()
+                .label
+                .as_deref(),
+            Some("FlipState")
+        );
+        assert_eq!(
+            edge(&graph, "processor0", "output0")
+                .unwrap()
+                .label
+                .as_deref(),
+            Some("BcmChangeBrightnessState")
+        );
+        assert!(edge(&graph, "state0", "output0").unwrap().dashed);
+    }
+
+    #[test]
+    fn graph_is_rendered_as_dot_and_mermaid() {
+        let graph = graph();
+
+        assert!(graph
+            .to_dot()
+            .contains("    state0 -> output0 [style=dashed];\n"));
+        assert!(graph
+            .to_mermaid()
+            .contains("    processor0 -->|\"FlipState\"| state0\n"));
+    }
+
+    #[test]
+    fn truncated_producer_data_falls_back_to_the_device() {
+        let inventory = Inventory::default();
+
+        assert_eq!(
+            output_label(
+                RELAY_SET_VALUE_PRODUCER_CODE,
+                &[0x00, 0x12],
+                "RelaySetValue".to_string(),
+                &inventory
+            ),
+            "0x0012"
+        );
+        assert_eq!(
+            output_label(
+                RELAY_SET_VALUE_PRODUCER_CODE,
+                &[0x00, 0x12, 0x03],
+                "RelaySetValue".to_string(),
+                &inventory
+            ),
+            "relay 3 on 0x0012"
+        );
+        assert_eq!(
+            output_label(
+                MESSAGE_PRODUCER_CODE,
+                &[0x00],
+                "Message".to_string(),
+                &inventory
+            ),
+            "Message"
+        );
+    }
+}
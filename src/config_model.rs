@@ -0,0 +1,348 @@
+use serde::Serialize;
+use std::fmt::Debug;
+
+use ross_config::config::Config;
+use ross_config::creator::Creator;
+use ross_config::event_processor::EventProcessor;
+use ross_config::matcher::Matcher;
+use ross_config::serializer::Serialize as ConfigSerialize;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ComponentModel {
+    pub code: u16,
+    pub name: String,
+    pub fields: String,
+    pub data: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum MatcherModel {
+    Single {
+        extractor: ComponentModel,
+        filter: ComponentModel,
+    },
+    Not {
+        matcher: Box<MatcherModel>,
+    },
+    Or {
+        left: Box<MatcherModel>,
+        right: Box<MatcherModel>,
+    },
+    And {
+        left: Box<MatcherModel>,
+        right: Box<MatcherModel>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CreatorModel {
+    pub extractor: ComponentModel,
+    pub producer: ComponentModel,
+    pub matcher: Option<MatcherModel>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EventProcessorModel {
+    pub matcher: MatcherModel,
+    pub creators: Vec<CreatorModel>,
+    pub size: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PeripheralModel {
+    pub index: u32,
+    pub peripheral: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StateModel {
+    pub index: u32,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ConfigModel {
+    pub size: usize,
+    pub peripherals: Vec<PeripheralModel>,
+    pub initial_state: Vec<StateModel>,
+    pub event_processors: Vec<EventProcessorModel>,
+}
+
+impl ComponentModel {
+    pub fn new<T: Debug + ConfigSerialize + ?Sized>(code: u16, component: &T) -> Self {
+        let debug = format!("{:?}", component);
+
+        let (name, fields) = match debug.find([' ', '(', '{']) {
+            Some(position) => (
+                debug[..position].to_string(),
+                debug[position..].trim().to_string(),
+            ),
+            None => (debug.clone(), String::new()),
+        };
+
+        Self {
+            code,
+            name,
+            fields,
+            data: to_hex(&component.serialize()),
+        }
+    }
+}
+
+impl std::fmt::Display for ComponentModel {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.fields.is_empty() {
+            write!(f, "{}", self.name)
+        } else {
+            write!(f, "{} {}", self.name, self.fields)
+        }
+    }
+}
+
+impl MatcherModel {
+    pub fn from_matcher(matcher: &Matcher) -> Self {
+        match matcher {
+            Matcher::Single { extractor, filter } => MatcherModel::Single {
+                extractor: ComponentModel::new(extractor.get_code(), extractor.as_ref()),
+                filter: ComponentModel::new(filter.get_code(), filter.as_ref()),
+            },
+            Matcher::Not(matcher) => MatcherModel::Not {
+                matcher: Box::new(Self::from_matcher(matcher)),
+            },
+            Matcher::Or(left, right) => MatcherModel::Or {
+                left: Box::new(Self::from_matcher(left)),
+                right: Box::new(Self::from_matcher(right)),
+            },
+            Matcher::And(left, right) => MatcherModel::And {
+                left: Box::new(Self::from_matcher(left)),
+                right: Box::new(Self::from_matcher(right)),
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for MatcherModel {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            MatcherModel::Single { extractor, filter } => write!(f, "{} -> {}", extractor, filter),
+            MatcherModel::Not { matcher } => write!(f, "not ({})", matcher),
+            MatcherModel::Or { left, right } => write!(f, "({}) or ({})", left, right),
+            MatcherModel::And { left, right } => write!(f, "({}) and ({})", left, right),
+        }
+    }
+}
+
+impl CreatorModel {
+    pub fn from_creator(creator: &Creator) -> Self {
+        Self {
+            extractor: ComponentModel::new(
+                creator.extractor.get_code(),
+                creator.extractor.as_ref(),
+            ),
+            producer: ComponentModel::new(creator.producer.get_code(), creator.producer.as_ref()),
+            matcher: creator.matcher.as_ref().map(MatcherModel::from_matcher),
+        }
+    }
+}
+
+impl std::fmt::Display for CreatorModel {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} -> {}", self.extractor, self.producer)?;
+
+        if let Some(matcher) = &self.matcher {
+            write!(f, " if {}", matcher)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl EventProcessorModel {
+    pub fn from_event_processor(event_processor: &EventProcessor) -> Self {
+        Self {
+            matcher: MatcherModel::from_matcher(&event_processor.matcher),
+            creators: event_processor
+                .creators
+                .iter()
+                .map(CreatorModel::from_creator)
+                .collect(),
+            size: event_processor_size(event_processor),
+        }
+    }
+}
+
+impl ConfigModel {
+    pub fn from_config(config: &Config, size: usize) -> Self {
+        Self {
+            size,
+            peripherals: config
+                .peripherals
+                .iter()
+                .map(|(index, peripheral)| PeripheralModel {
+                    index: *index,
+                    peripheral: format!("{:?}", peripheral),
+                })
+                .collect(),
+            initial_state: config
+                .initial_state
+                .iter()
+                .map(|(index, value)| StateModel {
+                    index: *index,
+                    value: format!("{:?}", value),
+                })
+                .collect(),
+            event_processors: config
+                .event_processors
+                .iter()
+                .map(EventProcessorModel::from_event_processor)
+                .collect(),
+        }
+    }
+
+    pub fn print(&self) {
+        println!("Config size: {} bytes", self.size);
+
+        println!("Peripherals ({}):", self.peripherals.len());
+        for peripheral in self.peripherals.iter() {
+            println!("  {}: {}", peripheral.index, peripheral.peripheral);
+        }
+
+        println!("Initial state ({}):", self.initial_state.len());
+        for state in self.initial_state.iter() {
+            println!("  {}: {}", state.index, state.value);
+        }
+
+        println!("Event processors ({}):", self.event_processors.len());
+        for (i, event_processor) in self.event_processors.iter().enumerate() {
+            println!("  #{} ({} bytes)", i, event_processor.size);
+            println!("    match {}", event_processor.matcher);
+
+            for creator in event_processor.creators.iter() {
+                println!("    fire {}", creator);
+            }
+        }
+    }
+}
+
+fn event_processor_size(event_processor: &EventProcessor) -> usize {
+    let mut size = 4 + event_processor.matcher.serialize().len() + 4;
+
+    for creator in event_processor.creators.iter() {
+        size += 2 + 1 + creator.extractor.serialize().len();
+        size += 2 + 1 + creator.producer.serialize().len();
+        size += 1;
+
+        if let Some(matcher) = &creator.matcher {
+            size += 4 + matcher.serialize().len();
+        }
+    }
+
+    size
+}
+
+fn to_hex(data: &[u8]) -> String {
+    data.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use ross_dsl::Parser;
+
+    fn model(text: &str) -> ConfigModel {
+        ConfigModel::from_config(&Parser::parse(text).unwrap(), 0)
+    }
+
+    fn component(code: u16, name: &str, fields: &str, data: &str) -> ComponentModel {
+        ComponentModel {
+            code,
+            name: name.to_string(),
+            fields: fields.to_string(),
+            data: data.to_string(),
+        }
+    }
+
+    const CONFIG: &str = "let light = false;
+        do {
+            match event 0x0002~u16;
+            match { ButtonIndexExtractor(); ValueEqualToConstFilter(2~u8); }
+            match { FlipStateFilter(light); }
+            fire { NoneExtractor(); BcmChangeBrightnessStateProducer(0x0012~u16, 1~u8, light); }
+            fire { PacketExtractor(); PacketProducer(0x0011~u16); }
+        }";
+
+    #[test]
+    fn components_are_split_into_name_and_fields() {
+        let model = model(CONFIG);
+        let event_processor = &model.event_processors[0];
+
+        let filters = match &event_processor.matcher {
+            MatcherModel::And { left, right } => match (left.as_ref(), right.as_ref()) {
+                (MatcherModel::And { right: button, .. }, MatcherModel::Single { filter, .. }) => {
+                    match button.as_ref() {
+                        MatcherModel::Single {
+                            extractor,
+                            filter: button_filter,
+                        } => vec![extractor.clone(), button_filter.clone(), filter.clone()],
+                        matcher => panic!("unexpected matcher {:?}", matcher),
+                    }
+                }
+                matchers => panic!("unexpected matchers {:?}", matchers),
+            },
+            matcher => panic!("unexpected matcher {:?}", matcher),
+        };
+
+        assert_eq!(
+            filters,
+            vec![
+                component(0x0006, "ButtonIndexExtractor", "", ""),
+                component(
+                    0x0000,
+                    "ValueEqualToConstFilter",
+                    "{ required_value: U8(2) }",
+                    "0002"
+                ),
+                component(0x0009, "FlipStateFilter", "{ state_index: 0 }", "00000000"),
+            ]
+        );
+        assert_eq!(
+            event_processor.creators[0],
+            CreatorModel {
+                extractor: component(0x0000, "NoneExtractor", "", ""),
+                producer: component(
+                    0x0004,
+                    "BcmChangeBrightnessStateProducer",
+                    "{ bcm_address: 18, index: 1, state_index: 0 }",
+                    "00120100000000"
+                ),
+                matcher: None,
+            }
+        );
+    }
+
+    #[test]
+    fn models_display_as_text() {
+        let model = model(CONFIG);
+        let event_processor = &model.event_processors[0];
+
+        assert_eq!(
+            event_processor.matcher.to_string(),
+            "((EventCodeExtractor -> ValueEqualToConstFilter { required_value: U16(2) }) \
+             and (ButtonIndexExtractor -> ValueEqualToConstFilter { required_value: U8(2) })) \
+             and (NoneExtractor -> FlipStateFilter { state_index: 0 })"
+        );
+        assert_eq!(
+            event_processor.creators[1].to_string(),
+            "PacketExtractor -> PacketProducer { receiver_address: 17 }"
+        );
+        assert_eq!(
+            model.initial_state,
+            vec![StateModel {
+                index: 0,
+                value: "Bool(false)".to_string(),
+            }]
+        );
+    }
+}
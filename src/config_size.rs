@@ -0,0 +1,189 @@
+use ross_config::config::Config;
+use ross_config::serializer::{ConfigSerializer, Serialize};
+
+use crate::config_model::ConfigModel;
+use crate::inventory::Inventory;
+use crate::ross_configurator::*;
+
+const COUNT_SIZE: usize = 4;
+const ENTRY_HEADER_SIZE: usize = 4 + 1;
+
+pub struct ConfigSizeBreakdown {
+    pub size: usize,
+    pub peripherals: (usize, usize),
+    pub initial_state: (usize, usize),
+    pub event_processors: (usize, usize),
+    pub largest_event_processors: Vec<(usize, usize, String)>,
+}
+
+impl ConfigSizeBreakdown {
+    pub fn new(config: &Config, size: usize) -> Self {
+        let model = ConfigModel::from_config(config, size);
+
+        let peripherals_size = COUNT_SIZE
+            + config
+                .peripherals
+                .values()
+                .map(|peripheral| ENTRY_HEADER_SIZE + peripheral.serialize().len())
+                .sum::<usize>();
+        let initial_state_size = COUNT_SIZE
+            + config
+                .initial_state
+                .values()
+                .map(|value| ENTRY_HEADER_SIZE + value.serialize().len())
+                .sum::<usize>();
+
+        let mut largest_event_processors: Vec<(usize, usize, String)> = model
+            .event_processors
+            .iter()
+            .enumerate()
+            .map(|(i, event_processor)| {
+                (i, event_processor.size, event_processor.matcher.to_string())
+            })
+            .collect();
+        largest_event_processors.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+        let event_processors_size = COUNT_SIZE
+            + largest_event_processors
+                .iter()
+                .map(|(_, size, _)| size)
+                .sum::<usize>();
+
+        Self {
+            size,
+            peripherals: (config.peripherals.len(), peripherals_size),
+            initial_state: (config.initial_state.len(), initial_state_size),
+            event_processors: (config.event_processors.len(), event_processors_size),
+            largest_event_processors,
+        }
+    }
+
+    pub fn print(&self, limit: usize) {
+        println!("Config size breakdown ({} bytes):", self.size);
+        println!(
+            "  peripherals ({}): {} bytes",
+            self.peripherals.0, self.peripherals.1
+        );
+        println!(
+            "  initial state ({}): {} bytes",
+            self.initial_state.0, self.initial_state.1
+        );
+        println!(
+            "  event processors ({}): {} bytes",
+            self.event_processors.0, self.event_processors.1
+        );
+
+        for (i, size, matcher) in self.largest_event_processors.iter().take(limit) {
+            println!("    #{} ({} bytes): match {}", i, size, matcher);
+        }
+
+        if self.largest_event_processors.len() > limit {
+            println!(
+                "    ... {} smaller event processors",
+                self.largest_event_processors.len() - limit
+            );
+        }
+    }
+}
+
+pub fn check_config_size(
+    config_data: &[u8],
+    address: u16,
+    inventory: &Inventory,
+) -> Result<(), ConfiguratorError> {
+    let max_config_size = match inventory.max_config_size(address) {
+        Some(max_config_size) => max_config_size,
+        None => return Ok(()),
+    };
+
+    if config_data.len() <= max_config_size {
+        return Ok(());
+    }
+
+    eprintln!(
+        "Config size {} bytes exceeds the max_config_size of {} bytes of device (address: {:#06x}) by {} bytes.",
+        config_data.len(),
+        max_config_size,
+        address,
+        config_data.len() - max_config_size
+    );
+
+    let config = ConfigSerializer::deserialize(config_data)
+        .map_err(ConfiguratorError::ConfigSerializerError)?;
+
+    ConfigSizeBreakdown::new(&config, config_data.len()).print(CONFIG_SIZE_BREAKDOWN_LIMIT);
+
+    Err(ConfiguratorError::ConfigTooLarge)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use ross_dsl::Parser;
+
+    const CONFIG: &str = "let light = false;
+        let ticks = 0~u32;
+        do { match tick; match { IncrementStateByConstFilter(ticks, 1~u32); } }
+        do {
+            match event 0x0002~u16;
+            match { FlipStateFilter(light); }
+            fire { PacketExtractor(); PacketProducer(0x0011~u16); }
+        }";
+
+    fn config_data() -> Vec<u8> {
+        ConfigSerializer::serialize(&Parser::parse(CONFIG).unwrap()).unwrap()
+    }
+
+    fn inventory(max_config_size: usize) -> Inventory {
+        toml::from_str(&format!(
+            "[profiles.small]\nmax_config_size = {}\n\n[[devices]]\naddress = 0x0010\nprofile = \"small\"\n",
+            max_config_size
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn breakdown_sections_add_up_to_the_config_size() {
+        let config = Parser::parse(CONFIG).unwrap();
+        let size = config_data().len();
+        let breakdown = ConfigSizeBreakdown::new(&config, size);
+
+        assert_eq!(breakdown.peripherals.0, 0);
+        assert_eq!(breakdown.initial_state.0, 2);
+        assert_eq!(breakdown.event_processors.0, 2);
+        assert_eq!(
+            breakdown.peripherals.1 + breakdown.initial_state.1 + breakdown.event_processors.1,
+            size
+        );
+    }
+
+    #[test]
+    fn largest_event_processors_come_first() {
+        let config = Parser::parse(CONFIG).unwrap();
+        let breakdown = ConfigSizeBreakdown::new(&config, config_data().len());
+
+        assert_eq!(
+            breakdown
+                .largest_event_processors
+                .iter()
+                .map(|(i, _, _)| *i)
+                .collect::<Vec<usize>>(),
+            vec![1, 0]
+        );
+        assert!(breakdown.largest_event_processors[0].1 > breakdown.largest_event_processors[1].1);
+    }
+
+    #[test]
+    fn configs_must_fit_the_device_profile() {
+        let config_data = config_data();
+
+        assert!(check_config_size(&config_data, 0x0010, &Inventory::default()).is_ok());
+        assert!(check_config_size(&config_data, 0x0011, &inventory(1)).is_ok());
+        assert!(check_config_size(&config_data, 0x0010, &inventory(config_data.len())).is_ok());
+        assert!(matches!(
+            check_config_size(&config_data, 0x0010, &inventory(config_data.len() - 1)),
+            Err(ConfiguratorError::ConfigTooLarge)
+        ));
+    }
+}
@@ -0,0 +1,667 @@
+use nom::character::complete::multispace0;
+use nom::sequence::preceded;
+use nom::{Err as NomErr, IResult};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryFrom;
+use std::fmt;
+use std::path::Path;
+
+use ross_config::Value;
+use ross_dsl::error::{ErrorKind, Expectation, ParserError};
+use ross_dsl::literal::Literal;
+use ross_dsl::statement::const_statement::const_statement;
+use ross_dsl::statement::do_statement::do_statement;
+use ross_dsl::statement::let_statement::let_statement;
+use ross_dsl::statement::peripheral_statement::peripheral_statement;
+use ross_dsl::statement::send_statement::send_statement;
+use ross_dsl::statement::set_statement::set_statement;
+use ross_protocol::event::event_code::*;
+
+use crate::preprocessor::PreprocessedSource;
+use crate::ross_configurator::*;
+
+const MAX_EXPECTATIONS: usize = 6;
+
+const EVENT_CODE_CONSTANTS: &[(&str, u16)] = &[
+    ("BOOTLOADER_HELLO_EVENT_CODE", BOOTLOADER_HELLO_EVENT_CODE),
+    ("PROGRAMMER_HELLO_EVENT_CODE", PROGRAMMER_HELLO_EVENT_CODE),
+    (
+        "PROGRAMMER_START_FIRMWARE_UPGRADE_EVENT_CODE",
+        PROGRAMMER_START_FIRMWARE_UPGRADE_EVENT_CODE,
+    ),
+    ("ACK_EVENT_CODE", ACK_EVENT_CODE),
+    ("DATA_EVENT_CODE", DATA_EVENT_CODE),
+    (
+        "CONFIGURATOR_HELLO_EVENT_CODE",
+        CONFIGURATOR_HELLO_EVENT_CODE,
+    ),
+    (
+        "BCM_CHANGE_BRIGHTNESS_EVENT_CODE",
+        BCM_CHANGE_BRIGHTNESS_EVENT_CODE,
+    ),
+    ("BUTTON_PRESSED_EVENT_CODE", BUTTON_PRESSED_EVENT_CODE),
+    ("BUTTON_RELEASED_EVENT_CODE", BUTTON_RELEASED_EVENT_CODE),
+    (
+        "INTERNAL_SYSTEM_TICK_EVENT_CODE",
+        INTERNAL_SYSTEM_TICK_EVENT_CODE,
+    ),
+    (
+        "PROGRAMMER_START_CONFIG_UPGRADE_EVENT_CODE",
+        PROGRAMMER_START_CONFIG_UPGRADE_EVENT_CODE,
+    ),
+    (
+        "PROGRAMMER_SET_DEVICE_ADDRESS_EVENT_CODE",
+        PROGRAMMER_SET_DEVICE_ADDRESS_EVENT_CODE,
+    ),
+    ("MESSAGE_EVENT_CODE", MESSAGE_EVENT_CODE),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    pub length: usize,
+    pub source_line: String,
+    pub label: String,
+    pub hint: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn new(
+        severity: Severity,
+        message: String,
+        file: &str,
+        source_line: &str,
+        (line, column): (usize, usize),
+        length: usize,
+    ) -> Self {
+        Self {
+            severity,
+            label: message.clone(),
+            message,
+            file: file.to_string(),
+            line,
+            column,
+            length: length.max(1),
+            source_line: source_line.trim_end().to_string(),
+            hint: None,
+        }
+    }
+
+    pub fn with_label(mut self, label: String) -> Self {
+        self.label = label;
+        self
+    }
+
+    pub fn with_hint(mut self, hint: Option<String>) -> Self {
+        self.hint = hint;
+        self
+    }
+
+    pub fn print(&self) {
+        eprintln!("{}", self);
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let gutter = " ".repeat(self.line.to_string().len());
+        let padding: String = self
+            .source_line
+            .chars()
+            .take(self.column - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+
+        writeln!(f, "{}: {}", self.severity, self.message)?;
+        writeln!(
+            f,
+            "{}--> {}:{}:{}",
+            gutter, self.file, self.line, self.column
+        )?;
+        writeln!(f, "{} |", gutter)?;
+        writeln!(f, "{} | {}", self.line, self.source_line)?;
+        write!(
+            f,
+            "{} | {}{} {}",
+            gutter,
+            padding,
+            "^".repeat(self.length),
+            self.label
+        )?;
+
+        if let Some(hint) = &self.hint {
+            writeln!(f)?;
+            writeln!(f, "{} |", gutter)?;
+            write!(f, "{} = hint: {}", gutter, hint)?;
+        }
+
+        Ok(())
+    }
+}
+
+pub fn print_diagnostics(path: &Path, diagnostics: &[Diagnostic]) {
+    for diagnostic in diagnostics.iter() {
+        diagnostic.print();
+        eprintln!();
+    }
+
+    eprintln!(
+        "error: could not parse {} due to {} previous error{}",
+        path.display(),
+        diagnostics.len(),
+        if diagnostics.len() == 1 { "" } else { "s" }
+    );
+}
+
+struct Declarations<'t> {
+    constants: BTreeMap<&'t str, Literal>,
+    state_variables: BTreeMap<&'t str, u32>,
+    state_count: u32,
+    failed: BTreeSet<&'t str>,
+}
+
+pub fn parse_diagnostics(source: &PreprocessedSource) -> Vec<Diagnostic> {
+    let text = remove_comments(&source.text);
+    let mut declarations = Declarations {
+        constants: EVENT_CODE_CONSTANTS
+            .iter()
+            .map(|(name, code)| (*name, Literal::U16(*code)))
+            .collect(),
+        state_variables: BTreeMap::new(),
+        state_count: 0,
+        failed: BTreeSet::new(),
+    };
+    let mut diagnostics = vec![];
+
+    for (start, end) in statement_spans(&text) {
+        if diagnostics.len() >= MAX_DIAGNOSTICS {
+            break;
+        }
+
+        let statement = &text[start..end];
+
+        if let Err(err) = parse_statement(statement, &mut declarations) {
+            let cascaded = statement
+                .split(|c: char| !c.is_alphanumeric() && c != '_')
+                .any(|word| declarations.failed.contains(word));
+
+            if let Some(name) = declared_name(statement) {
+                declarations.failed.insert(name);
+            }
+
+            if cascaded {
+                continue;
+            }
+
+            diagnostics.push(parser_error_diagnostic(source, &text[..end], start, &err));
+        }
+    }
+
+    diagnostics
+}
+
+// Mirrors the statement loop of `ross_dsl::Parser::parse` for a single statement, so that every
+// statement is parsed once against the declarations that precede it.
+fn parse_statement<'t>(
+    mut text: &'t str,
+    declarations: &mut Declarations<'t>,
+) -> Result<(), ParserError<String>> {
+    while !text.is_empty() {
+        if let Some((input, _)) = alternative(preceded(
+            multispace0,
+            peripheral_statement(&declarations.constants),
+        )(text))?
+        {
+            text = input;
+            continue;
+        }
+
+        if let Some((input, (name, value))) =
+            alternative(preceded(multispace0, let_statement)(text))?
+        {
+            Value::try_from(value)?;
+            declarations
+                .state_variables
+                .insert(name, declarations.state_count);
+            declarations
+                .constants
+                .insert(name, Literal::U32(declarations.state_count));
+            declarations.state_count += 1;
+            text = input;
+            continue;
+        }
+
+        if let Some((input, (name, value))) =
+            alternative(preceded(multispace0, const_statement)(text))?
+        {
+            declarations.constants.insert(name, value);
+            text = input;
+            continue;
+        }
+
+        if let Some((input, _)) = alternative(preceded(
+            multispace0,
+            send_statement(&declarations.constants),
+        )(text))?
+        {
+            text = input;
+            continue;
+        }
+
+        if let Some((input, _)) =
+            alternative(preceded(multispace0, do_statement(&declarations.constants))(text))?
+        {
+            text = input;
+            continue;
+        }
+
+        if let Some((input, _)) = alternative(preceded(
+            multispace0,
+            set_statement(&declarations.constants, &declarations.state_variables),
+        )(text))?
+        {
+            text = input;
+            continue;
+        }
+
+        let trimmed = text.trim_start_matches([' ', '\t', '\r', '\n']);
+
+        if trimmed.len() != text.len() {
+            text = trimmed;
+            continue;
+        }
+
+        return Err(ParserError::Base {
+            location: text.to_string(),
+            kind: ErrorKind::Expected(Expectation::Something),
+            child: None,
+        });
+    }
+
+    Ok(())
+}
+
+fn declared_name(statement: &str) -> Option<&str> {
+    let rest = ["let", "const"]
+        .iter()
+        .find_map(|keyword| statement.strip_prefix(keyword))?;
+
+    if !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+
+    let rest = rest.trim_start();
+    let end = rest
+        .find(|c: char| !c.is_alphanumeric() && c != '_')
+        .unwrap_or(rest.len());
+
+    if end == 0 {
+        None
+    } else {
+        Some(&rest[..end])
+    }
+}
+
+fn alternative<'t, T>(
+    result: IResult<&'t str, T, ParserError<&'t str>>,
+) -> Result<Option<(&'t str, T)>, ParserError<String>> {
+    match result {
+        Ok(output) => Ok(Some(output)),
+        Err(NomErr::Failure(err)) => Err(err.into()),
+        Err(_) => Ok(None),
+    }
+}
+
+fn parser_error_diagnostic(
+    source: &PreprocessedSource,
+    text: &str,
+    statement_start: usize,
+    err: &ParserError<String>,
+) -> Diagnostic {
+    let mut nodes = vec![];
+    collect_nodes(text, err, statement_start, &mut nodes);
+
+    let offset = nodes
+        .iter()
+        .map(|(offset, _)| *offset)
+        .max()
+        .unwrap_or(statement_start);
+
+    let furthest: Vec<&ErrorKind> = nodes
+        .iter()
+        .filter(|(node_offset, _)| *node_offset == offset)
+        .map(|(_, kind)| *kind)
+        .collect();
+
+    let primary = furthest
+        .iter()
+        .find(|kind| !matches!(kind, ErrorKind::Nom(_)))
+        .copied();
+
+    let mut expectations: Vec<String> = vec![];
+
+    for kind in furthest.iter() {
+        if let ErrorKind::Expected(
+            expectation @ Expectation::Keyword(_) | expectation @ Expectation::Symbol(_),
+        ) = kind
+        {
+            let expectation = expectation.to_string();
+
+            if !expectations.contains(&expectation) {
+                expectations.push(expectation);
+            }
+        }
+    }
+
+    let token = token_at(text, offset);
+    let found = if token.is_empty() {
+        "end of file".to_string()
+    } else {
+        format!("`{}`", token)
+    };
+
+    let message = match primary {
+        Some(kind @ ErrorKind::Expected(_)) => format!("{}, found {}", kind, found),
+        Some(kind) => kind.to_string(),
+        None => format!("unexpected {}", found),
+    };
+
+    let alternatives = matches!(
+        primary,
+        Some(ErrorKind::Expected(Expectation::Keyword(_)))
+            | Some(ErrorKind::Expected(Expectation::Symbol(_)))
+    );
+
+    let label = if alternatives && expectations.len() > 1 {
+        let mut label = format!(
+            "expected one of {}",
+            expectations
+                .iter()
+                .take(MAX_EXPECTATIONS)
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+
+        if expectations.len() > MAX_EXPECTATIONS {
+            label += ", ...";
+        }
+
+        label
+    } else {
+        match primary {
+            Some(kind) => kind.to_string(),
+            None => "unexpected input".to_string(),
+        }
+    };
+
+    source
+        .diagnostic(
+            Severity::Error,
+            message,
+            line_column(text, offset),
+            token.chars().count(),
+        )
+        .with_label(label)
+        .with_hint(primary.and_then(hint))
+}
+
+fn collect_nodes<'a>(
+    text: &str,
+    err: &'a ParserError<String>,
+    parent_offset: usize,
+    nodes: &mut Vec<(usize, &'a ErrorKind)>,
+) {
+    match err {
+        ParserError::Base {
+            location,
+            kind,
+            child,
+        } => {
+            let offset = if !location.is_empty() && text.ends_with(location.as_str()) {
+                text.len() - location.len()
+            } else if !location.is_empty() {
+                text[parent_offset..]
+                    .find(location.as_str())
+                    .map(|i| parent_offset + i)
+                    .unwrap_or(parent_offset)
+            } else {
+                parent_offset
+            };
+
+            nodes.push((offset, kind));
+
+            if let Some(child) = child {
+                collect_nodes(text, child, offset, nodes);
+            }
+        }
+        ParserError::Alt(siblings) => {
+            for sibling in siblings.iter() {
+                collect_nodes(text, sibling, parent_offset, nodes);
+            }
+        }
+    }
+}
+
+fn hint(kind: &ErrorKind) -> Option<String> {
+    let hint = match kind {
+        ErrorKind::Expected(Expectation::Symbol(';')) => "statements end with `;`",
+        ErrorKind::Expected(Expectation::Literal) | ErrorKind::Expected(Expectation::Value) => {
+            "literals look like `true`, `5~u8`, `0x0010~u16` or `#ff8000`"
+        }
+        ErrorKind::Expected(Expectation::Type) => "types follow a `~`, e.g. `5~u8` or `0x0010~u16`",
+        ErrorKind::Expected(Expectation::StateVariable) => {
+            "state variables are declared with `let name = value;` before they are used"
+        }
+        ErrorKind::Expected(Expectation::Something) => {
+            "statements start with `let`, `const`, `peripheral`, `send`, `set` or `do` and end with `;`"
+        }
+        ErrorKind::Expected(Expectation::ArgumentCount(_, _)) => {
+            "check the number of arguments passed"
+        }
+        ErrorKind::UnknownExtractor => {
+            "extractor names are case-sensitive, e.g. `EventCodeExtractor`"
+        }
+        ErrorKind::UnknownFilter => "filter names are case-sensitive, e.g. `FlipStateFilter`",
+        ErrorKind::UnknownProducer => "producer names are case-sensitive, e.g. `PacketProducer`",
+        ErrorKind::CastFromToNotAllowed(_, _) => {
+            "add or change the literal's type suffix, e.g. `5~u8`"
+        }
+        _ => return None,
+    };
+
+    Some(hint.to_string())
+}
+
+pub fn line_column(text: &str, offset: usize) -> (usize, usize) {
+    let offset = offset.min(text.len());
+    let line_start = text[..offset].rfind('\n').map(|i| i + 1).unwrap_or(0);
+
+    (
+        text[..line_start].matches('\n').count() + 1,
+        text[line_start..offset].chars().count() + 1,
+    )
+}
+
+fn token_at(text: &str, offset: usize) -> &str {
+    let rest = &text[offset.min(text.len())..];
+    let first = match rest.chars().next() {
+        Some(first) if !first.is_whitespace() => first,
+        _ => return "",
+    };
+
+    if !first.is_alphanumeric() && first != '_' {
+        return &rest[..first.len_utf8()];
+    }
+
+    let end = rest
+        .find(|c: char| !c.is_alphanumeric() && c != '_' && c != '~')
+        .unwrap_or(rest.len());
+
+    &rest[..end]
+}
+
+fn remove_comments(source: &str) -> String {
+    let mut text = String::new();
+
+    for line in source.lines() {
+        text += line.split("//").next().unwrap_or_default();
+        text += "\n";
+    }
+
+    text
+}
+
+fn statement_spans(text: &str) -> Vec<(usize, usize)> {
+    let mut spans = vec![];
+    let mut start = None;
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        if start.is_none() {
+            if c.is_whitespace() {
+                continue;
+            }
+
+            start = Some(i);
+        }
+
+        let end = match c {
+            '"' => {
+                in_string = !in_string;
+                None
+            }
+            _ if in_string => None,
+            '{' => {
+                depth += 1;
+                None
+            }
+            '}' if depth > 1 || !is_do_statement(&text[start.unwrap_or(i)..]) => {
+                depth = if depth > 0 { depth - 1 } else { 0 };
+                None
+            }
+            '}' => {
+                depth = 0;
+
+                let mut end = i + 1;
+
+                while let Some((j, next)) = chars.peek().copied() {
+                    if next == ';' {
+                        chars.next();
+                        end = j + 1;
+                        break;
+                    } else if next.is_whitespace() {
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+
+                Some(end)
+            }
+            ';' if depth == 0 => Some(i + 1),
+            _ => None,
+        };
+
+        if let (Some(statement_start), Some(end)) = (start, end) {
+            spans.push((statement_start, end));
+            start = None;
+        }
+    }
+
+    if let Some(statement_start) = start {
+        spans.push((statement_start, text.len()));
+    }
+
+    spans
+}
+
+fn is_do_statement(statement: &str) -> bool {
+    statement.starts_with("do")
+        && !statement[2..].starts_with(|c: char| c.is_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::preprocessor::{preprocess_text, Definitions};
+
+    fn diagnostics(text: &str) -> Vec<Diagnostic> {
+        let source =
+            preprocess_text(Path::new("test.ross"), text, &Definitions::default()).unwrap();
+
+        parse_diagnostics(&source)
+    }
+
+    #[test]
+    fn valid_configs_have_no_diagnostics() {
+        let text = "let light = false;\nconst LAMP = 0x0012~u16;\ndo {\n    match event BUTTON_PRESSED_EVENT_CODE;\n    match { FlipStateFilter(light); }\n    fire { NoneExtractor(); PacketProducer(LAMP); }\n}\n";
+
+        assert!(diagnostics(text).is_empty());
+    }
+
+    #[test]
+    fn every_broken_statement_is_reported_once() {
+        let text =
+            "let a = ;\nlet b = true;\nsend 0x0001~u16 from to 0x0050~u16;\nlet c = false;\n";
+        let diagnostics = diagnostics(text);
+
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].line, 1);
+        assert_eq!(diagnostics[1].line, 3);
+    }
+
+    #[test]
+    fn failed_declarations_do_not_cascade() {
+        let text =
+            "let light = ;\ndo {\n    match tick;\n    match { FlipStateFilter(light); }\n}\n";
+        let diagnostics = diagnostics(text);
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].line, 1);
+    }
+
+    #[test]
+    fn undeclared_state_variables_are_reported() {
+        let text = "do {\n    match tick;\n    match { FlipStateFilter(light); }\n}\n";
+
+        assert_eq!(diagnostics(text).len(), 1);
+    }
+
+    #[test]
+    fn comments_are_ignored() {
+        assert!(diagnostics("// let a = ;\nlet a = true; // trailing ;\n").is_empty());
+    }
+
+    #[test]
+    fn declared_names_are_extracted() {
+        assert_eq!(declared_name("let light = ;"), Some("light"));
+        assert_eq!(declared_name("const LAMP ="), Some("LAMP"));
+        assert_eq!(declared_name("letter = 1;"), None);
+        assert_eq!(declared_name("send 0x0001~u16;"), None);
+    }
+}
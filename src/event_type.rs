@@ -1,5 +1,7 @@
 use clap::arg_enum;
 
+use ross_protocol::event::event_code::*;
+
 arg_enum! {
     #[derive(Debug, PartialEq)]
     pub enum EventType {
@@ -20,3 +22,46 @@ arg_enum! {
         SystemTick
     }
 }
+
+impl EventType {
+    pub fn code(&self) -> u16 {
+        match self {
+            EventType::Ack => ACK_EVENT_CODE,
+            EventType::Data => DATA_EVENT_CODE,
+            EventType::ConfiguratorHello => CONFIGURATOR_HELLO_EVENT_CODE,
+            EventType::BootloaderHello => BOOTLOADER_HELLO_EVENT_CODE,
+            EventType::ProgrammerHello => PROGRAMMER_HELLO_EVENT_CODE,
+            EventType::ProgrammerStartFirmwareUpgrade => {
+                PROGRAMMER_START_FIRMWARE_UPGRADE_EVENT_CODE
+            }
+            EventType::ProgrammerStartConfigUpgrade => PROGRAMMER_START_CONFIG_UPGRADE_EVENT_CODE,
+            EventType::ButtonPressed => BUTTON_PRESSED_EVENT_CODE,
+            EventType::ButtonReleased => BUTTON_RELEASED_EVENT_CODE,
+            EventType::SystemTick => INTERNAL_SYSTEM_TICK_EVENT_CODE,
+        }
+    }
+
+    pub fn constant_name(&self) -> &'static str {
+        match self {
+            EventType::Ack => "ACK_EVENT_CODE",
+            EventType::Data => "DATA_EVENT_CODE",
+            EventType::ConfiguratorHello => "CONFIGURATOR_HELLO_EVENT_CODE",
+            EventType::BootloaderHello => "BOOTLOADER_HELLO_EVENT_CODE",
+            EventType::ProgrammerHello => "PROGRAMMER_HELLO_EVENT_CODE",
+            EventType::ProgrammerStartFirmwareUpgrade => {
+                "PROGRAMMER_START_FIRMWARE_UPGRADE_EVENT_CODE"
+            }
+            EventType::ProgrammerStartConfigUpgrade => "PROGRAMMER_START_CONFIG_UPGRADE_EVENT_CODE",
+            EventType::ButtonPressed => "BUTTON_PRESSED_EVENT_CODE",
+            EventType::ButtonReleased => "BUTTON_RELEASED_EVENT_CODE",
+            EventType::SystemTick => "INTERNAL_SYSTEM_TICK_EVENT_CODE",
+        }
+    }
+
+    pub fn all() -> Vec<EventType> {
+        EventType::variants()
+            .iter()
+            .filter_map(|variant| variant.parse().ok())
+            .collect()
+    }
+}
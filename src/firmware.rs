@@ -0,0 +1,302 @@
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::convert::{TryFrom, TryInto};
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+
+use crate::ledger::Ledger;
+use crate::ross_configurator::*;
+
+pub const FIRMWARE_HEADER_MAGIC: &[u8; 4] = b"RFWH";
+pub const FIRMWARE_MANIFEST_EXTENSION: &str = "toml";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FirmwareMetadata {
+    pub board: String,
+    pub version: Version,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FirmwareManifest {
+    pub image: String,
+    pub board: String,
+    pub version: Version,
+}
+
+#[derive(Debug)]
+pub struct Firmware {
+    pub data: Vec<u8>,
+    pub metadata: Option<FirmwareMetadata>,
+}
+
+impl Firmware {
+    pub fn load(path: &Path) -> Result<Self, ConfiguratorError> {
+        if path.extension().and_then(|extension| extension.to_str())
+            == Some(FIRMWARE_MANIFEST_EXTENSION)
+        {
+            Self::load_manifest(path)
+        } else {
+            Self::load_image(path)
+        }
+    }
+
+    fn load_manifest(path: &Path) -> Result<Self, ConfiguratorError> {
+        let manifest: FirmwareManifest =
+            toml::from_str(&fs::read_to_string(path).map_err(ConfiguratorError::IOError)?)
+                .map_err(ConfiguratorError::TomlError)?;
+
+        let image_path = match path.parent() {
+            Some(parent) => parent.join(&manifest.image),
+            None => Path::new(&manifest.image).to_path_buf(),
+        };
+
+        let image = Self::load_image(&image_path)?;
+
+        let metadata = FirmwareMetadata {
+            board: manifest.board,
+            version: manifest.version,
+        };
+
+        if let Some(header_metadata) = image.metadata {
+            if header_metadata != metadata {
+                eprintln!(
+                    "Firmware manifest ({} {}) does not match image header ({} {}).",
+                    metadata.board,
+                    metadata.version,
+                    header_metadata.board,
+                    header_metadata.version
+                );
+                return Err(ConfiguratorError::InvalidFirmwareMetadata);
+            }
+        }
+
+        Ok(Self {
+            data: image.data,
+            metadata: Some(metadata),
+        })
+    }
+
+    fn load_image(path: &Path) -> Result<Self, ConfiguratorError> {
+        let mut file = File::open(path).map_err(ConfiguratorError::IOError)?;
+
+        let mut buf = vec![];
+        file.read_to_end(&mut buf)
+            .map_err(ConfiguratorError::IOError)?;
+
+        if !buf.starts_with(FIRMWARE_HEADER_MAGIC) {
+            return Ok(Self {
+                data: buf,
+                metadata: None,
+            });
+        }
+
+        let (metadata, header_len) = parse_header(&buf)?;
+
+        Ok(Self {
+            data: buf.split_off(header_len),
+            metadata: Some(metadata),
+        })
+    }
+}
+
+// Header layout: magic, major (u16), minor (u16), patch (u16), board length (u8), board.
+pub fn encode_header(metadata: &FirmwareMetadata) -> Option<Vec<u8>> {
+    let version = &metadata.version;
+
+    if !version.pre.is_empty() || !version.build.is_empty() {
+        return None;
+    }
+
+    let mut buf = FIRMWARE_HEADER_MAGIC.to_vec();
+
+    for part in [version.major, version.minor, version.patch].iter() {
+        buf.extend_from_slice(&u16::try_from(*part).ok()?.to_be_bytes());
+    }
+
+    buf.push(u8::try_from(metadata.board.len()).ok()?);
+    buf.extend_from_slice(metadata.board.as_bytes());
+
+    Some(buf)
+}
+
+fn parse_header(buf: &[u8]) -> Result<(FirmwareMetadata, usize), ConfiguratorError> {
+    let mut offset = FIRMWARE_HEADER_MAGIC.len();
+
+    if buf.len() < offset + 7 {
+        eprintln!("Firmware header is truncated.");
+        return Err(ConfiguratorError::InvalidFirmwareMetadata);
+    }
+
+    let mut version_parts = [0; 3];
+
+    for part in version_parts.iter_mut() {
+        *part = u16::from_be_bytes(buf[offset..offset + 2].try_into().unwrap()) as u64;
+        offset += 2;
+    }
+
+    let board_len = buf[offset] as usize;
+    offset += 1;
+
+    if buf.len() < offset + board_len {
+        eprintln!("Firmware header is truncated.");
+        return Err(ConfiguratorError::InvalidFirmwareMetadata);
+    }
+
+    let board = match String::from_utf8(buf[offset..offset + board_len].to_vec()) {
+        Ok(board) => board,
+        Err(_) => {
+            eprintln!("Firmware header board type is not valid UTF-8.");
+            return Err(ConfiguratorError::InvalidFirmwareMetadata);
+        }
+    };
+    offset += board_len;
+
+    let metadata = FirmwareMetadata {
+        board,
+        version: Version::new(version_parts[0], version_parts[1], version_parts[2]),
+    };
+
+    Ok((metadata, offset))
+}
+
+pub fn check_firmware(
+    firmware: &Firmware,
+    ledger: &Ledger,
+    address: u16,
+    board: Option<&str>,
+    allow_downgrade: bool,
+    force: bool,
+) -> Result<(), ConfiguratorError> {
+    let metadata = match &firmware.metadata {
+        Some(metadata) => metadata,
+        None if force => {
+            println!("Firmware has no version metadata, skipping compatibility checks.");
+            return Ok(());
+        }
+        None => {
+            eprintln!(
+                "Firmware has no version metadata, so it cannot be checked against device (address: {:#06x}), refusing to flash it without --force.",
+                address
+            );
+            return Err(ConfiguratorError::IncompatibleFirmware);
+        }
+    };
+
+    let entry = ledger.get(address);
+
+    let device_board = match (board, entry) {
+        (Some(board), _) => board,
+        (None, Some(entry)) => entry.board.as_str(),
+        (None, None) => {
+            eprintln!(
+                "Board type of device (address: {:#06x}) is unknown, specify it with --board.",
+                address
+            );
+            return Err(ConfiguratorError::UnknownBoard);
+        }
+    };
+
+    if metadata.board != device_board {
+        eprintln!(
+            "Firmware is built for board {}, but device (address: {:#06x}) is a {}.",
+            metadata.board, address, device_board
+        );
+        return Err(ConfiguratorError::IncompatibleFirmware);
+    }
+
+    if let Some(Some(current_version)) = entry.map(|entry| &entry.version) {
+        if metadata.version < *current_version && !allow_downgrade {
+            eprintln!(
+                "Device (address: {:#06x}) is running firmware {}, refusing to downgrade to {} without --allow-downgrade.",
+                address, current_version, metadata.version
+            );
+            return Err(ConfiguratorError::FirmwareDowngrade);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(board: &str, version: (u16, u16, u16)) -> Vec<u8> {
+        let mut buf = FIRMWARE_HEADER_MAGIC.to_vec();
+        buf.extend_from_slice(&version.0.to_be_bytes());
+        buf.extend_from_slice(&version.1.to_be_bytes());
+        buf.extend_from_slice(&version.2.to_be_bytes());
+        buf.push(board.len() as u8);
+        buf.extend_from_slice(board.as_bytes());
+
+        buf
+    }
+
+    #[test]
+    fn header_is_parsed() {
+        let mut buf = header("relay-4", (1, 4, 2));
+        buf.extend_from_slice(&[0xaa, 0xbb]);
+
+        let (metadata, header_len) = parse_header(&buf).unwrap();
+
+        assert_eq!(metadata.board, "relay-4");
+        assert_eq!(metadata.version, Version::new(1, 4, 2));
+        assert_eq!(&buf[header_len..], &[0xaa, 0xbb]);
+    }
+
+    #[test]
+    fn encoded_header_is_parsed_back() {
+        let metadata = FirmwareMetadata {
+            board: "relay-4".to_string(),
+            version: Version::new(1, 4, 2),
+        };
+        let buf = encode_header(&metadata).unwrap();
+
+        assert_eq!(buf, header("relay-4", (1, 4, 2)));
+        assert_eq!(parse_header(&buf).unwrap(), (metadata, buf.len()));
+    }
+
+    #[test]
+    fn unrepresentable_versions_have_no_header() {
+        let metadata = |version: &str| FirmwareMetadata {
+            board: "relay-4".to_string(),
+            version: Version::parse(version).unwrap(),
+        };
+
+        assert!(encode_header(&metadata("1.5.0-rc.1")).is_none());
+        assert!(encode_header(&metadata("70000.0.0")).is_none());
+    }
+
+    #[test]
+    fn truncated_header_is_rejected() {
+        let buf = header("relay-4", (1, 4, 2));
+
+        assert!(parse_header(&buf[..8]).is_err());
+        assert!(parse_header(&buf[..buf.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn non_utf8_board_is_rejected() {
+        let mut buf = header("ab", (1, 0, 0));
+        let len = buf.len();
+        buf[len - 1] = 0xff;
+
+        assert!(parse_header(&buf).is_err());
+    }
+
+    #[test]
+    fn firmware_without_metadata_requires_force() {
+        let firmware = Firmware {
+            data: vec![0xaa, 0xbb],
+            metadata: None,
+        };
+        let ledger = Ledger::default();
+
+        assert!(matches!(
+            check_firmware(&firmware, &ledger, 0x0010, None, false, false),
+            Err(ConfiguratorError::IncompatibleFirmware)
+        ));
+        assert!(check_firmware(&firmware, &ledger, 0x0010, None, false, true).is_ok());
+    }
+}
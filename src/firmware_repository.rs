@@ -0,0 +1,314 @@
+use semver::{Version, VersionReq};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use crate::firmware::{Firmware, FirmwareManifest, FirmwareMetadata, FIRMWARE_MANIFEST_EXTENSION};
+use crate::ross_configurator::*;
+
+pub const LATEST_VERSION: &str = "latest";
+pub const LATEST_PRERELEASE_VERSION: &str = "latest-pre";
+
+pub enum VersionSelector {
+    Latest,
+    LatestPrerelease,
+    Exact(Version),
+    Requirement(VersionReq),
+}
+
+impl VersionSelector {
+    pub fn parse(string: &str) -> Option<Self> {
+        if string == LATEST_VERSION {
+            return Some(VersionSelector::Latest);
+        }
+
+        if string == LATEST_PRERELEASE_VERSION {
+            return Some(VersionSelector::LatestPrerelease);
+        }
+
+        if let Ok(version) = Version::parse(string) {
+            return Some(VersionSelector::Exact(version));
+        }
+
+        VersionReq::parse(string)
+            .ok()
+            .map(VersionSelector::Requirement)
+    }
+
+    pub fn matches(&self, version: &Version) -> bool {
+        match self {
+            VersionSelector::Latest => version.pre.is_empty(),
+            VersionSelector::LatestPrerelease => true,
+            VersionSelector::Exact(exact) => version == exact,
+            VersionSelector::Requirement(requirement) => requirement.matches(version),
+        }
+    }
+}
+
+pub struct FirmwareRepository {
+    root: PathBuf,
+}
+
+impl FirmwareRepository {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    pub fn default_path() -> PathBuf {
+        data_directory().join(FIRMWARE_REPOSITORY_DIRECTORY_NAME)
+    }
+
+    pub fn list(&self, board: Option<&str>) -> Result<Vec<FirmwareMetadata>, ConfiguratorError> {
+        if let Some(board) = board {
+            check_board_name(board, ConfiguratorError::UnknownBoard)?;
+        }
+
+        let mut releases = vec![];
+
+        for board_directory in read_directory(&self.root)? {
+            if !board_directory.is_dir() {
+                continue;
+            }
+
+            let board_name = match board_directory.file_name().and_then(|name| name.to_str()) {
+                Some(board_name) => board_name.to_string(),
+                None => continue,
+            };
+
+            if board.is_some_and(|board| board != board_name) {
+                continue;
+            }
+
+            for path in read_directory(&board_directory)? {
+                if path.extension().and_then(|extension| extension.to_str())
+                    != Some(FIRMWARE_MANIFEST_EXTENSION)
+                {
+                    continue;
+                }
+
+                let version = path
+                    .file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .and_then(|stem| Version::parse(stem).ok());
+
+                if let Some(version) = version {
+                    releases.push(FirmwareMetadata {
+                        board: board_name.clone(),
+                        version,
+                    });
+                }
+            }
+        }
+
+        releases.sort_by(|a, b| a.board.cmp(&b.board).then(a.version.cmp(&b.version)));
+
+        Ok(releases)
+    }
+
+    pub fn import(&self, path: &Path) -> Result<FirmwareMetadata, ConfiguratorError> {
+        let firmware = Firmware::load(path)?;
+
+        let metadata = match firmware.metadata {
+            Some(metadata) => metadata,
+            None => {
+                eprintln!("Firmware has no version metadata and cannot be imported.");
+                return Err(ConfiguratorError::InvalidFirmwareMetadata);
+            }
+        };
+
+        check_board_name(&metadata.board, ConfiguratorError::InvalidFirmwareMetadata)?;
+
+        let board_directory = self.root.join(&metadata.board);
+        fs::create_dir_all(&board_directory).map_err(ConfiguratorError::IOError)?;
+
+        let image_name = format!("{}.bin", metadata.version);
+        let manifest = toml::to_string(&FirmwareManifest {
+            image: image_name.clone(),
+            board: metadata.board.clone(),
+            version: metadata.version.clone(),
+        })
+        .map_err(ConfiguratorError::TomlSerializeError)?;
+
+        fs::write(board_directory.join(&image_name), &firmware.data)
+            .map_err(ConfiguratorError::IOError)?;
+        fs::write(self.manifest_path(&metadata), manifest).map_err(ConfiguratorError::IOError)?;
+
+        Ok(metadata)
+    }
+
+    pub fn select(
+        &self,
+        board: &str,
+        selector: &VersionSelector,
+    ) -> Result<PathBuf, ConfiguratorError> {
+        let release = self
+            .list(Some(board))?
+            .into_iter()
+            .rfind(|release| selector.matches(&release.version));
+
+        match release {
+            Some(release) => Ok(self.manifest_path(&release)),
+            None => Err(ConfiguratorError::FirmwareNotFound),
+        }
+    }
+
+    pub fn prune(
+        &self,
+        board: Option<&str>,
+        keep: usize,
+    ) -> Result<Vec<FirmwareMetadata>, ConfiguratorError> {
+        let releases = self.list(board)?;
+        let mut pruned = vec![];
+
+        for (i, release) in releases.iter().enumerate() {
+            let newer_count = releases[i + 1..]
+                .iter()
+                .filter(|newer| newer.board == release.board)
+                .count();
+
+            if newer_count < keep {
+                continue;
+            }
+
+            fs::remove_file(self.manifest_path(release)).map_err(ConfiguratorError::IOError)?;
+            fs::remove_file(self.image_path(release)).map_err(ConfiguratorError::IOError)?;
+
+            pruned.push(release.clone());
+        }
+
+        Ok(pruned)
+    }
+
+    fn manifest_path(&self, release: &FirmwareMetadata) -> PathBuf {
+        self.root.join(&release.board).join(format!(
+            "{}.{}",
+            release.version, FIRMWARE_MANIFEST_EXTENSION
+        ))
+    }
+
+    fn image_path(&self, release: &FirmwareMetadata) -> PathBuf {
+        self.root
+            .join(&release.board)
+            .join(format!("{}.bin", release.version))
+    }
+}
+
+pub fn is_valid_board_name(board: &str) -> bool {
+    !board.is_empty()
+        && board
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+pub fn check_board_name(board: &str, error: ConfiguratorError) -> Result<(), ConfiguratorError> {
+    if is_valid_board_name(board) {
+        return Ok(());
+    }
+
+    eprintln!(
+        "Board type \"{}\" may only contain letters, digits, '_' and '-'.",
+        board
+    );
+
+    Err(error)
+}
+
+fn read_directory(path: &Path) -> Result<Vec<PathBuf>, ConfiguratorError> {
+    let entries = match fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => return Err(ConfiguratorError::IOError(err)),
+    };
+
+    let mut paths = vec![];
+
+    for entry in entries {
+        paths.push(entry.map_err(ConfiguratorError::IOError)?.path());
+    }
+
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+    use std::process;
+
+    fn version(string: &str) -> Version {
+        Version::parse(string).unwrap()
+    }
+
+    #[test]
+    fn latest_excludes_prereleases_unless_asked() {
+        let latest = VersionSelector::parse(LATEST_VERSION).unwrap();
+        let latest_prerelease = VersionSelector::parse(LATEST_PRERELEASE_VERSION).unwrap();
+
+        assert!(latest.matches(&version("1.2.0")));
+        assert!(!latest.matches(&version("1.3.0-rc.1")));
+        assert!(latest_prerelease.matches(&version("1.3.0-rc.1")));
+    }
+
+    #[test]
+    fn requirements_and_exact_versions_match() {
+        let requirement = VersionSelector::parse("1.4.x").unwrap();
+        let exact = VersionSelector::parse("1.4.2").unwrap();
+
+        assert!(requirement.matches(&version("1.4.7")));
+        assert!(!requirement.matches(&version("1.5.0")));
+        assert!(exact.matches(&version("1.4.2")));
+        assert!(!exact.matches(&version("1.4.3")));
+        assert!(VersionSelector::parse("not a version").is_none());
+    }
+
+    #[test]
+    fn board_names_are_restricted() {
+        assert!(is_valid_board_name("relay_4-v2"));
+        assert!(!is_valid_board_name(""));
+        assert!(!is_valid_board_name("../etc"));
+        assert!(!is_valid_board_name("a\"b"));
+        assert!(!is_valid_board_name("a/b"));
+    }
+
+    #[test]
+    fn imported_releases_are_listed_and_selected() {
+        let root = env::temp_dir().join(format!(
+            "ross_configurator_firmware_repository_{}",
+            process::id()
+        ));
+        let manifest_path = root.join("bundle.toml");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("image.bin"), [1, 2, 3]).unwrap();
+
+        let repository = FirmwareRepository::new(root.join("repository"));
+
+        for release in ["1.0.0", "1.1.0-rc.1"].iter() {
+            fs::write(
+                &manifest_path,
+                format!(
+                    "image = \"image.bin\"\nboard = \"relay\"\nversion = \"{}\"\n",
+                    release
+                ),
+            )
+            .unwrap();
+            repository.import(&manifest_path).unwrap();
+        }
+
+        let releases = repository.list(Some("relay")).unwrap();
+        let latest = repository
+            .select("relay", &VersionSelector::Latest)
+            .unwrap();
+        let latest_prerelease = repository
+            .select("relay", &VersionSelector::LatestPrerelease)
+            .unwrap();
+        let firmware = Firmware::load(&latest).unwrap();
+
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(releases.len(), 2);
+        assert_eq!(firmware.data, vec![1, 2, 3]);
+        assert_eq!(firmware.metadata.unwrap().version, version("1.0.0"));
+        assert!(latest_prerelease.ends_with("1.1.0-rc.1.toml"));
+    }
+}
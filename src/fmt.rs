@@ -0,0 +1,697 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+use ross_config::serializer::ConfigSerializer;
+use ross_dsl::Parser;
+
+use crate::config_file::parse_source;
+use crate::preprocessor::{
+    aliases_path, const_directive, directive_argument, load_aliases, preprocess_text, Definitions,
+    INCLUDE_DIRECTIVE,
+};
+use crate::ross_configurator::*;
+
+const INDENT: &str = "    ";
+const SYMBOLS: &[char] = &['{', '}', '(', ')', ';', ',', '='];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Equivalence {
+    Equivalent,
+    Unchecked,
+    Changed,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Symbol(char),
+    Comment(String, bool),
+    BlankLine,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum StatementKind {
+    Include,
+    Comment,
+    Directive,
+    Const,
+    Let,
+    Peripheral,
+    EventProcessor,
+}
+
+struct Statement {
+    kind: StatementKind,
+    comments: Vec<String>,
+    blank_before: bool,
+    tokens: Vec<Token>,
+}
+
+impl Statement {
+    fn new(comments: Vec<String>, blank_before: bool) -> Self {
+        Self {
+            kind: StatementKind::EventProcessor,
+            comments,
+            blank_before,
+            tokens: vec![],
+        }
+    }
+
+    fn is_block(&self) -> bool {
+        self.tokens.contains(&Token::Symbol('{'))
+    }
+
+    fn is_barrier(&self) -> bool {
+        matches!(self.kind, StatementKind::Include | StatementKind::Comment)
+    }
+
+    fn declared_name(&self) -> Option<String> {
+        match (self.kind, self.tokens.first(), self.tokens.get(1)) {
+            (StatementKind::Const, _, Some(Token::Word(name)))
+            | (StatementKind::Let, _, Some(Token::Word(name))) => Some(name.clone()),
+            (StatementKind::Directive, Some(Token::Word(directive)), _) => {
+                const_directive(directive)
+                    .and_then(|(_, argument)| argument.split('=').next())
+                    .map(|name| name.trim().to_string())
+            }
+            _ => None,
+        }
+    }
+}
+
+struct Printer {
+    output: String,
+    indent: usize,
+    paren_depth: usize,
+    line_start: bool,
+    pending_newline: bool,
+    pending_blank_line: bool,
+    previous: Option<Token>,
+}
+
+impl Printer {
+    fn new() -> Self {
+        Self {
+            output: String::new(),
+            indent: 0,
+            paren_depth: 0,
+            line_start: true,
+            pending_newline: false,
+            pending_blank_line: false,
+            previous: None,
+        }
+    }
+
+    fn print_statement(&mut self, statement: &Statement) {
+        self.indent = 0;
+        self.paren_depth = 0;
+        self.previous = None;
+
+        for comment in statement.comments.iter() {
+            self.print_line(&format!("//{}", comment));
+        }
+
+        for token in statement.tokens.iter() {
+            self.print_token(token);
+        }
+
+        self.pending_newline = true;
+        self.pending_blank_line = false;
+        self.flush_newline();
+    }
+
+    fn print_line(&mut self, line: &str) {
+        self.flush_newline();
+        self.write_indent();
+        self.output += line;
+        self.pending_newline = true;
+    }
+
+    fn print_token(&mut self, token: &Token) {
+        match token {
+            Token::Comment(comment, true) => {
+                if self.previous == Some(Token::Symbol('{')) {
+                    self.pending_blank_line = false;
+                }
+
+                self.print_line(&format!("//{}", comment));
+                return;
+            }
+            Token::Comment(comment, false) => {
+                self.output += " //";
+                self.output += comment;
+                self.pending_newline = true;
+                return;
+            }
+            Token::BlankLine => {
+                if self.pending_newline && self.previous != Some(Token::Symbol('{')) {
+                    self.pending_blank_line = true;
+                }
+                return;
+            }
+            Token::Symbol('}') => {
+                self.indent = self.indent.saturating_sub(1);
+                self.pending_blank_line = false;
+                self.pending_newline = true;
+            }
+            Token::Symbol(',') | Token::Symbol(';')
+                if self.previous == Some(Token::Symbol('}')) =>
+            {
+                self.pending_newline = false;
+            }
+            Token::Word(word) if word == "if" && self.previous == Some(Token::Symbol('}')) => {
+                self.pending_newline = false;
+            }
+            _ => {}
+        }
+
+        if self.pending_newline {
+            self.flush_newline();
+        }
+
+        if self.line_start {
+            self.write_indent();
+        } else if self.needs_space(token) {
+            self.output.push(' ');
+        }
+
+        match token {
+            Token::Word(word) => self.output += word,
+            Token::Symbol(symbol) => self.output.push(*symbol),
+            _ => {}
+        }
+
+        match token {
+            Token::Symbol('{') => {
+                self.indent += 1;
+                self.pending_newline = true;
+            }
+            Token::Symbol('}') => self.pending_newline = true,
+            Token::Symbol('(') => self.paren_depth += 1,
+            Token::Symbol(')') => self.paren_depth = self.paren_depth.saturating_sub(1),
+            Token::Symbol(';') | Token::Symbol(',') if self.paren_depth == 0 => {
+                self.pending_newline = true;
+            }
+            _ => {}
+        }
+
+        self.previous = Some(token.clone());
+    }
+
+    fn needs_space(&self, token: &Token) -> bool {
+        !matches!(
+            (&self.previous, token),
+            (None, _)
+                | (Some(Token::Symbol('(')), _)
+                | (_, Token::Symbol(')'))
+                | (_, Token::Symbol(';'))
+                | (_, Token::Symbol(','))
+                | (Some(Token::Word(_)), Token::Symbol('('))
+        )
+    }
+
+    fn flush_newline(&mut self) {
+        if !self.pending_newline {
+            return;
+        }
+
+        self.output.push('\n');
+
+        if self.pending_blank_line {
+            self.output.push('\n');
+        }
+
+        self.line_start = true;
+        self.pending_newline = false;
+        self.pending_blank_line = false;
+    }
+
+    fn write_indent(&mut self) {
+        self.output += &INDENT.repeat(self.indent);
+        self.line_start = false;
+    }
+
+    fn blank_line(&mut self) {
+        self.output.push('\n');
+    }
+}
+
+pub fn format_source(text: &str, reorder: bool) -> String {
+    let mut statements = split_statements(text);
+
+    let mut names = BTreeSet::new();
+    let redeclares = statements
+        .iter()
+        .filter_map(Statement::declared_name)
+        .any(|name| !names.insert(name));
+
+    if reorder && !redeclares {
+        for section in statements.split_mut(Statement::is_barrier) {
+            section.sort_by_key(|statement| statement.kind);
+        }
+    }
+
+    let mut printer = Printer::new();
+    let mut previous: Option<&Statement> = None;
+
+    for statement in statements.iter() {
+        if let Some(previous) = previous {
+            if statement.blank_before
+                || statement.kind != previous.kind
+                || statement.is_block()
+                || previous.is_block()
+            {
+                printer.blank_line();
+            }
+        }
+
+        printer.print_statement(statement);
+        previous = Some(statement);
+    }
+
+    printer.output
+}
+
+pub fn fmt(paths: &[&Path], check: bool) -> Result<(), ConfiguratorError> {
+    let mut unformatted_count = 0;
+    let mut unchanged_count = 0;
+
+    for path in paths.iter() {
+        let text = fs::read_to_string(path).map_err(ConfiguratorError::IOError)?;
+        let mut formatted = format_source(&text, true);
+
+        if formatted == text {
+            continue;
+        }
+
+        match check_equivalent(path, &text, &formatted)? {
+            Equivalence::Equivalent => {}
+            Equivalence::Unchecked => {
+                eprintln!(
+                    "{} has preprocessor errors, so formatting cannot be checked against the config it describes, leaving it unchanged.",
+                    path.display()
+                );
+                unchanged_count += 1;
+                continue;
+            }
+            Equivalence::Changed => {
+                eprintln!(
+                    "Reordering statements in {} would change the config it describes, formatting without reordering.",
+                    path.display()
+                );
+                formatted = format_source(&text, false);
+
+                if formatted == text {
+                    continue;
+                }
+
+                if check_equivalent(path, &text, &formatted)? != Equivalence::Equivalent {
+                    eprintln!(
+                        "Formatting {} would change the config it describes, leaving it unchanged.",
+                        path.display()
+                    );
+                    unchanged_count += 1;
+                    continue;
+                }
+            }
+        }
+
+        if check {
+            let line = text
+                .lines()
+                .zip(formatted.lines())
+                .position(|(line, formatted_line)| line != formatted_line)
+                .unwrap_or_else(|| text.lines().count().min(formatted.lines().count()));
+
+            println!(
+                "{} is not formatted (first difference at line {}).",
+                path.display(),
+                line + 1
+            );
+            unformatted_count += 1;
+        } else {
+            fs::write(path, &formatted).map_err(ConfiguratorError::IOError)?;
+            println!("Formatted {}.", path.display());
+        }
+    }
+
+    if unchanged_count > 0 {
+        eprintln!(
+            "{} of {} config{} could not be formatted safely.",
+            unchanged_count,
+            paths.len(),
+            if paths.len() == 1 { "" } else { "s" }
+        );
+        return Err(ConfiguratorError::FormatterError);
+    }
+
+    if unformatted_count > 0 {
+        eprintln!(
+            "{} of {} config{} not formatted, run `fmt` without `--check` to fix.",
+            unformatted_count,
+            paths.len(),
+            if paths.len() == 1 { " is" } else { "s are" }
+        );
+        return Err(ConfiguratorError::NotFormatted);
+    }
+
+    Ok(())
+}
+
+fn check_equivalent(
+    path: &Path,
+    text: &str,
+    formatted: &str,
+) -> Result<Equivalence, ConfiguratorError> {
+    let definitions = Definitions {
+        aliases: load_aliases(&aliases_path())?,
+        ..Definitions::default()
+    };
+
+    let source = preprocess_text(path, text, &definitions)?;
+
+    if !source.diagnostics.is_empty() {
+        return Ok(Equivalence::Unchecked);
+    }
+
+    let config_data = ConfigSerializer::serialize(&parse_source(path, &source)?).ok();
+
+    let formatted_source = preprocess_text(path, formatted, &definitions)?;
+    let formatted_config_data = if formatted_source.diagnostics.is_empty() {
+        Parser::parse(&formatted_source.text)
+            .ok()
+            .and_then(|config| ConfigSerializer::serialize(&config).ok())
+    } else {
+        None
+    };
+
+    if formatted_config_data.is_none() || formatted_config_data != config_data {
+        return Ok(Equivalence::Changed);
+    }
+
+    Ok(Equivalence::Equivalent)
+}
+
+fn split_statements(text: &str) -> Vec<Statement> {
+    let mut statements: Vec<Statement> = vec![];
+    let mut current: Option<Statement> = None;
+    let mut depth = 0;
+    let mut comments = vec![];
+    let mut blank_before = false;
+
+    for line in text.lines() {
+        let (code, comment) = match line.find("//") {
+            Some(i) => (&line[..i], Some(line[i + 2..].trim_end().to_string())),
+            None => (line, None),
+        };
+        let trimmed = code.trim();
+
+        if trimmed.is_empty() {
+            match (&mut current, comment) {
+                (Some(statement), Some(comment)) => {
+                    statement.tokens.push(Token::Comment(comment, true))
+                }
+                (Some(statement), None) => statement.tokens.push(Token::BlankLine),
+                (None, Some(comment)) => comments.push(comment),
+                (None, None) if !comments.is_empty() => {
+                    statements.push(Statement {
+                        kind: StatementKind::Comment,
+                        comments: std::mem::take(&mut comments),
+                        blank_before,
+                        tokens: vec![],
+                    });
+                    blank_before = true;
+                }
+                (None, None) => blank_before = !statements.is_empty(),
+            }
+            continue;
+        }
+
+        if current.is_none() {
+            if let Some(kind) = directive_kind(trimmed) {
+                let mut tokens = vec![Token::Word(format_directive(trimmed))];
+                tokens.extend(comment.map(|comment| Token::Comment(comment, false)));
+
+                statements.push(Statement {
+                    kind,
+                    comments: std::mem::take(&mut comments),
+                    blank_before,
+                    tokens,
+                });
+                blank_before = false;
+                continue;
+            }
+        }
+
+        for token in tokenize(trimmed) {
+            let statement = current.get_or_insert_with(|| {
+                let statement = Statement::new(std::mem::take(&mut comments), blank_before);
+                blank_before = false;
+                statement
+            });
+
+            let ends_statement = match token {
+                Token::Symbol('{') => {
+                    depth += 1;
+                    false
+                }
+                Token::Symbol('}') => {
+                    depth = if depth > 0 { depth - 1 } else { 0 };
+                    depth == 0
+                }
+                Token::Symbol(';') => depth == 0,
+                _ => false,
+            };
+
+            if statement.tokens.is_empty() {
+                statement.kind = statement_kind(&token);
+            }
+
+            statement.tokens.push(token);
+
+            if ends_statement {
+                statements.extend(current.take());
+            }
+        }
+
+        if let Some(comment) = comment {
+            let statement = match current.as_mut() {
+                Some(statement) => statement,
+                None => match statements.last_mut() {
+                    Some(statement) => statement,
+                    None => continue,
+                },
+            };
+
+            statement.tokens.push(Token::Comment(comment, false));
+        }
+    }
+
+    statements.extend(current);
+
+    if !comments.is_empty() {
+        statements.push(Statement {
+            kind: StatementKind::Comment,
+            comments,
+            blank_before,
+            tokens: vec![],
+        });
+    }
+
+    for statement in statements.iter_mut() {
+        while statement.tokens.last() == Some(&Token::BlankLine) {
+            statement.tokens.pop();
+        }
+    }
+
+    statements
+}
+
+fn tokenize(code: &str) -> Vec<Token> {
+    let mut tokens = vec![];
+    let mut chars = code.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+
+        if SYMBOLS.contains(&c) {
+            tokens.push(Token::Symbol(c));
+            continue;
+        }
+
+        let mut word = c.to_string();
+        let mut in_string = c == '"';
+        let mut in_placeholder = c == '$' && chars.peek() == Some(&'{');
+
+        while let Some(&next) = chars.peek() {
+            if !in_string && !in_placeholder && (next.is_whitespace() || SYMBOLS.contains(&next)) {
+                break;
+            }
+
+            if next == '"' {
+                in_string = !in_string;
+            } else if next == '$' && !in_string {
+                word.push(next);
+                chars.next();
+                in_placeholder = chars.peek() == Some(&'{');
+                continue;
+            } else if next == '}' && in_placeholder {
+                in_placeholder = false;
+            }
+
+            word.push(next);
+            chars.next();
+        }
+
+        tokens.push(Token::Word(word));
+    }
+
+    tokens
+}
+
+fn statement_kind(token: &Token) -> StatementKind {
+    match token {
+        Token::Word(word) if word == "const" => StatementKind::Const,
+        Token::Word(word) if word == "let" => StatementKind::Let,
+        Token::Word(word) if word == "peripheral" || word == "pub" => StatementKind::Peripheral,
+        _ => StatementKind::EventProcessor,
+    }
+}
+
+fn directive_kind(line: &str) -> Option<StatementKind> {
+    if directive_argument(line, INCLUDE_DIRECTIVE).is_some() {
+        Some(StatementKind::Include)
+    } else if const_directive(line).is_some() {
+        Some(StatementKind::Directive)
+    } else {
+        None
+    }
+}
+
+fn format_directive(line: &str) -> String {
+    if let Some(argument) = directive_argument(line, INCLUDE_DIRECTIVE) {
+        return format!(
+            "{} {}",
+            INCLUDE_DIRECTIVE,
+            argument.trim_end_matches(';').trim_end()
+        );
+    }
+
+    match const_directive(line).and_then(|(directive, argument)| {
+        argument
+            .split_once('=')
+            .map(|(name, value)| (directive, name, value))
+    }) {
+        Some((directive, name, value)) => {
+            format!("{} {} = {}", directive, name.trim(), value.trim())
+        }
+        None => line.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+    use std::process;
+
+    #[test]
+    fn declarations_are_moved_before_event_processors() {
+        let text = "send 0x0001~u16 from 0x0123~u16 to 0x0050~u16;\nlet light = false;\n";
+
+        assert_eq!(
+            format_source(text, true),
+            "let light = false;\n\nsend 0x0001~u16 from 0x0123~u16 to 0x0050~u16;\n"
+        );
+    }
+
+    #[test]
+    fn statements_keep_their_order_without_reordering() {
+        let text = "send 0x0001~u16 from 0x0123~u16 to 0x0050~u16;\nlet light = false;\n";
+
+        assert_eq!(
+            format_source(text, false),
+            "send 0x0001~u16 from 0x0123~u16 to 0x0050~u16;\n\nlet light = false;\n"
+        );
+    }
+
+    #[test]
+    fn blocks_are_indented() {
+        let text = "do {\nmatch tick;\n  match { IncrementStateByConstFilter(ticks, 1~u32); }\n}\n";
+
+        assert_eq!(
+            format_source(text, true),
+            "do {\n    match tick;\n    match {\n        IncrementStateByConstFilter(ticks, 1~u32);\n    }\n}\n"
+        );
+    }
+
+    #[test]
+    fn comments_stay_with_their_statement() {
+        let text = "send 0x0001~u16 from 0x0123~u16 to 0x0050~u16;\n// Kitchen light\nlet light = false;\n";
+        let formatted = format_source(text, true);
+
+        assert!(formatted.starts_with("// Kitchen light\nlet light = false;\n"));
+    }
+
+    #[test]
+    fn redeclared_names_prevent_reordering() {
+        let text = "let a = 1~u8;\nsend 0x0001~u16 from 0x0123~u16 to 0x0050~u16;\nlet a = 2~u8;\n";
+
+        assert!(format_source(text, true).ends_with("let a = 2~u8;\n"));
+    }
+
+    #[test]
+    fn preprocessor_constants_keep_their_directive() {
+        let text = "const LIGHT=0x0010\n@const  ROOM = 2\nconst X = 1~u8;\n";
+
+        assert_eq!(
+            format_source(text, false),
+            "const LIGHT = 0x0010\n@const ROOM = 2\n\nconst X = 1~u8;\n"
+        );
+    }
+
+    #[test]
+    fn equivalence_reports_why_formatting_is_unsafe() {
+        let path = Path::new("main.ross");
+        let text = "let light = false;\nlet other = true;\n";
+
+        assert_eq!(
+            check_equivalent(path, text, "let light = false;\n\nlet other = true;\n").unwrap(),
+            Equivalence::Equivalent
+        );
+        assert_eq!(
+            check_equivalent(path, text, "let other = true;\nlet light = false;\n").unwrap(),
+            Equivalence::Changed
+        );
+        assert_eq!(
+            check_equivalent(path, "const = 1\nlet light = false;\n", "").unwrap(),
+            Equivalence::Unchecked
+        );
+    }
+
+    #[test]
+    fn files_with_preprocessor_errors_are_left_unchanged() {
+        let path = env::temp_dir().join(format!("ross_configurator_fmt_{}.ross", process::id()));
+        let text = "const = 1\nlet  light=false;\n";
+        fs::write(&path, text).unwrap();
+
+        let result = fmt(&[path.as_path()], false);
+        let formatted = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert!(matches!(result, Err(ConfiguratorError::FormatterError)));
+        assert_eq!(formatted, text);
+    }
+
+    #[test]
+    fn formatting_is_idempotent() {
+        let text = "let  light=false;\ndo {\nmatch tick;\n}\nconst X = 1~u8;\n";
+        let formatted = format_source(text, true);
+
+        assert_eq!(format_source(&formatted, true), formatted);
+    }
+}
@@ -1,7 +1,7 @@
 use std::collections::BTreeSet;
 use std::iter::IntoIterator;
 use std::thread::sleep;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use ross_protocol::convert_packet::ConvertPacket;
 use ross_protocol::event::bootloader::*;
@@ -9,11 +9,28 @@ use ross_protocol::event::programmer::*;
 use ross_protocol::interface::serial::Serial;
 use ross_protocol::protocol::Protocol;
 
+use crate::cancellation::CancellationToken;
 use crate::ross_configurator::*;
 
 pub fn get_devices(
     protocol: &mut Protocol<Serial>,
     programmer: &ProgrammerHelloEvent,
+) -> Result<BTreeSet<BootloaderHelloEvent>, ConfiguratorError> {
+    let devices = discover_devices(protocol, programmer)?;
+
+    for bootloader_hello_event in devices.iter() {
+        println!(
+            "Found device (address: {:#06x})",
+            bootloader_hello_event.bootloader_address
+        );
+    }
+
+    Ok(devices)
+}
+
+pub fn discover_devices(
+    protocol: &mut Protocol<Serial>,
+    programmer: &ProgrammerHelloEvent,
 ) -> Result<BTreeSet<BootloaderHelloEvent>, ConfiguratorError> {
     let devices: Vec<BootloaderHelloEvent> = match protocol.exchange_packets(
         programmer.to_packet(),
@@ -24,14 +41,33 @@ pub fn get_devices(
         Err(err) => return Err(ConfiguratorError::ProtocolError(err)),
     };
 
-    let devices: BTreeSet<BootloaderHelloEvent> = devices.into_iter().collect();
+    Ok(devices.into_iter().collect())
+}
 
-    for bootloader_hello_event in devices.iter() {
-        println!(
-            "Found device (address: {:#06x})",
-            bootloader_hello_event.bootloader_address
-        );
-    }
+pub fn wait_for_devices(
+    protocol: &mut Protocol<Serial>,
+    programmer: &ProgrammerHelloEvent,
+    addresses: &[u16],
+    timeout: Duration,
+    cancellation: &CancellationToken,
+) -> Result<BTreeSet<u16>, ConfiguratorError> {
+    let start = Instant::now();
+    let mut found = BTreeSet::new();
 
-    Ok(devices)
+    loop {
+        for device in discover_devices(protocol, programmer)?.iter() {
+            if addresses.contains(&device.bootloader_address) {
+                found.insert(device.bootloader_address);
+            }
+        }
+
+        if found.len() == addresses.len() || start.elapsed() >= timeout {
+            return Ok(found);
+        }
+
+        if !cancellation.sleep(Duration::from_millis(REDISCOVERY_INTERVAL_MS)) {
+            eprintln!("Cancelled waiting for devices to reappear.");
+            return Err(ConfiguratorError::WaitCancelled);
+        }
+    }
 }
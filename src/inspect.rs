@@ -0,0 +1,37 @@
+use std::path::Path;
+
+use ross_config::serializer::ConfigSerializer;
+
+use crate::config_file::load_config_data;
+use crate::config_model::ConfigModel;
+use crate::ross_configurator::*;
+
+pub fn load_config_model(
+    path: &Path,
+    env: Option<&str>,
+    require_checksum: bool,
+) -> Result<ConfigModel, ConfiguratorError> {
+    let config_data = load_config_data(path, env, require_checksum)?;
+    let config = ConfigSerializer::deserialize(&config_data)
+        .map_err(ConfiguratorError::ConfigSerializerError)?;
+
+    Ok(ConfigModel::from_config(&config, config_data.len()))
+}
+
+pub fn inspect(
+    path: &Path,
+    json: bool,
+    env: Option<&str>,
+    require_checksum: bool,
+) -> Result<(), ConfiguratorError> {
+    let model = load_config_model(path, env, require_checksum)?;
+
+    if json {
+        let string = serde_json::to_string_pretty(&model).map_err(ConfiguratorError::JsonError)?;
+        println!("{}", string);
+    } else {
+        model.print();
+    }
+
+    Ok(())
+}
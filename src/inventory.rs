@@ -0,0 +1,212 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use crate::ross_configurator::*;
+
+pub const ADAPTIVE_CHUNK_SIZE: &str = "adaptive";
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(try_from = "RawChunkSize")]
+pub enum ChunkSize {
+    Fixed(usize),
+    Adaptive,
+}
+
+impl ChunkSize {
+    pub fn parse(string: &str) -> Option<Self> {
+        if string == ADAPTIVE_CHUNK_SIZE {
+            return Some(ChunkSize::Adaptive);
+        }
+
+        match parse_int::parse::<usize>(string) {
+            Ok(size) => Self::try_from(RawChunkSize::Bytes(size)).ok(),
+            Err(_) => None,
+        }
+    }
+}
+
+impl Default for ChunkSize {
+    fn default() -> Self {
+        ChunkSize::Fixed(DATA_PACKET_SIZE)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawChunkSize {
+    Bytes(usize),
+    Mode(String),
+}
+
+impl TryFrom<RawChunkSize> for ChunkSize {
+    type Error = String;
+
+    fn try_from(raw: RawChunkSize) -> Result<Self, Self::Error> {
+        match raw {
+            RawChunkSize::Bytes(size) if (1..=MAX_CHUNK_SIZE).contains(&size) => {
+                Ok(ChunkSize::Fixed(size))
+            }
+            RawChunkSize::Bytes(size) => Err(format!(
+                "chunk size {} is not between 1 and {}",
+                size, MAX_CHUNK_SIZE
+            )),
+            RawChunkSize::Mode(mode) if mode == ADAPTIVE_CHUNK_SIZE => Ok(ChunkSize::Adaptive),
+            RawChunkSize::Mode(mode) => Err(format!("unknown chunk size mode \"{}\"", mode)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct DeviceProfile {
+    pub chunk_size: Option<ChunkSize>,
+    pub max_config_size: Option<usize>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct InventoryDevice {
+    pub address: u16,
+    pub name: Option<String>,
+    pub profile: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct Inventory {
+    #[serde(default)]
+    pub profiles: BTreeMap<String, DeviceProfile>,
+    #[serde(default)]
+    pub devices: Vec<InventoryDevice>,
+}
+
+impl Inventory {
+    pub fn default_path() -> PathBuf {
+        data_directory().join(INVENTORY_FILE_NAME)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, ConfiguratorError> {
+        let inventory: Self = match fs::read_to_string(path) {
+            Ok(string) => toml::from_str(&string).map_err(ConfiguratorError::TomlError)?,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(ConfiguratorError::IOError(err)),
+        };
+
+        for device in inventory.devices.iter() {
+            if let Some(profile) = &device.profile {
+                if !inventory.profiles.contains_key(profile) {
+                    eprintln!(
+                        "Device (address: {:#06x}) uses unknown profile \"{}\".",
+                        device.address, profile
+                    );
+                    return Err(ConfiguratorError::UnknownProfile);
+                }
+            }
+        }
+
+        Ok(inventory)
+    }
+
+    pub fn device(&self, address: u16) -> Option<&InventoryDevice> {
+        self.devices.iter().find(|device| device.address == address)
+    }
+
+    pub fn profile(&self, address: u16) -> Option<&DeviceProfile> {
+        self.device(address)
+            .and_then(|device| device.profile.as_ref())
+            .and_then(|profile| self.profiles.get(profile))
+    }
+
+    pub fn max_config_size(&self, address: u16) -> Option<usize> {
+        self.profile(address)
+            .and_then(|profile| profile.max_config_size)
+    }
+
+    pub fn chunk_size(&self, address: u16, chunk_size: Option<ChunkSize>) -> ChunkSize {
+        chunk_size
+            .or_else(|| self.profile(address).and_then(|profile| profile.chunk_size))
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+    use std::process;
+
+    const INVENTORY: &str = "[profiles.small]\n\
+                             chunk_size = 64\n\
+                             max_config_size = 512\n\
+                             \n\
+                             [profiles.flaky]\n\
+                             chunk_size = \"adaptive\"\n\
+                             \n\
+                             [[devices]]\n\
+                             address = 0x0010\n\
+                             name = \"hallway\"\n\
+                             profile = \"small\"\n\
+                             \n\
+                             [[devices]]\n\
+                             address = 0x0011\n\
+                             profile = \"flaky\"\n\
+                             \n\
+                             [[devices]]\n\
+                             address = 0x0012\n";
+
+    #[test]
+    fn chunk_sizes_are_parsed_within_bounds() {
+        assert_eq!(ChunkSize::parse("64"), Some(ChunkSize::Fixed(64)));
+        assert_eq!(
+            ChunkSize::parse("0x400"),
+            Some(ChunkSize::Fixed(MAX_CHUNK_SIZE))
+        );
+        assert_eq!(ChunkSize::parse("adaptive"), Some(ChunkSize::Adaptive));
+        assert_eq!(ChunkSize::parse("0"), None);
+        assert_eq!(ChunkSize::parse("1025"), None);
+        assert_eq!(ChunkSize::parse("-1"), None);
+        assert_eq!(ChunkSize::parse("fast"), None);
+    }
+
+    #[test]
+    fn profile_chunk_sizes_are_validated() {
+        assert!(toml::from_str::<DeviceProfile>("chunk_size = 0").is_err());
+        assert!(toml::from_str::<DeviceProfile>("chunk_size = \"fast\"").is_err());
+    }
+
+    #[test]
+    fn settings_come_from_the_device_profile() {
+        let inventory: Inventory = toml::from_str(INVENTORY).unwrap();
+
+        assert_eq!(inventory.chunk_size(0x0010, None), ChunkSize::Fixed(64));
+        assert_eq!(inventory.chunk_size(0x0011, None), ChunkSize::Adaptive);
+        assert_eq!(inventory.chunk_size(0x0012, None), ChunkSize::default());
+        assert_eq!(inventory.chunk_size(0x0013, None), ChunkSize::default());
+        assert_eq!(
+            inventory.chunk_size(0x0010, Some(ChunkSize::Fixed(256))),
+            ChunkSize::Fixed(256)
+        );
+        assert_eq!(inventory.max_config_size(0x0010), Some(512));
+        assert_eq!(inventory.max_config_size(0x0011), None);
+    }
+
+    #[test]
+    fn unknown_profiles_are_rejected() {
+        let path = env::temp_dir().join(format!(
+            "ross_configurator_inventory_{}.toml",
+            process::id()
+        ));
+        fs::write(
+            &path,
+            "[[devices]]\naddress = 0x0010\nprofile = \"missing\"\n",
+        )
+        .unwrap();
+
+        let result = Inventory::load(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert!(matches!(result, Err(ConfiguratorError::UnknownProfile)));
+    }
+}
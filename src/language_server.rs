@@ -0,0 +1,746 @@
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use ross_dsl::Parser;
+
+use crate::diagnostics::{parse_diagnostics, Diagnostic, Severity};
+use crate::event_type::EventType;
+use crate::inventory::Inventory;
+use crate::preprocessor::{
+    aliases_path, directive_argument, load_aliases, preprocess_text, Definitions,
+    PreprocessedSource, CONST_DIRECTIVE_ALIAS,
+};
+use crate::ross_configurator::*;
+
+const FILE_URI_PREFIX: &str = "file://";
+const PARSE_ERROR_CODE: i64 = -32700;
+const METHOD_NOT_FOUND_ERROR_CODE: i64 = -32601;
+const FULL_TEXT_DOCUMENT_SYNC: u64 = 1;
+const VARIABLE_COMPLETION_KIND: u64 = 6;
+const ENUM_MEMBER_COMPLETION_KIND: u64 = 20;
+const CONSTANT_COMPLETION_KIND: u64 = 21;
+const LET_KEYWORD: &str = "let";
+const CONST_KEYWORD: &str = "const";
+
+#[derive(Debug, Clone, PartialEq)]
+enum DefinitionKind {
+    State(u32),
+    Constant,
+}
+
+#[derive(Debug, Clone)]
+struct Definition {
+    name: String,
+    kind: DefinitionKind,
+    value: String,
+    file: String,
+    line: usize,
+    column: usize,
+    line_text: String,
+}
+
+pub struct LanguageServer {
+    documents: BTreeMap<String, String>,
+    inventory: Inventory,
+    definitions: Definitions,
+    shutdown: bool,
+}
+
+impl LanguageServer {
+    pub fn new() -> Self {
+        let inventory = Inventory::load(&Inventory::default_path()).unwrap_or_else(|err| {
+            eprintln!("Failed to load the device inventory: {:?}", err);
+            Inventory::default()
+        });
+        let aliases = load_aliases(&aliases_path()).unwrap_or_else(|err| {
+            eprintln!("Failed to load aliases: {:?}", err);
+            BTreeMap::new()
+        });
+
+        Self {
+            documents: BTreeMap::new(),
+            inventory,
+            definitions: Definitions {
+                aliases,
+                ..Definitions::default()
+            },
+            shutdown: false,
+        }
+    }
+
+    pub fn run(&mut self) -> Result<(), ConfiguratorError> {
+        let stdin = io::stdin();
+        let mut reader = stdin.lock();
+        let stdout = io::stdout();
+        let mut writer = stdout.lock();
+
+        while let Some(body) = read_message(&mut reader)? {
+            let message: Value = match serde_json::from_slice(&body) {
+                Ok(message) => message,
+                Err(err) => {
+                    eprintln!("Ignoring malformed language server message: {}", err);
+                    write_message(
+                        &mut writer,
+                        &json!({
+                            "jsonrpc": "2.0",
+                            "id": Value::Null,
+                            "error": {
+                                "code": PARSE_ERROR_CODE,
+                                "message": format!("malformed message: {}", err),
+                            },
+                        }),
+                    )?;
+                    continue;
+                }
+            };
+
+            if message["method"] == "exit" {
+                break;
+            }
+
+            for response in self.handle(&message) {
+                write_message(&mut writer, &response)?;
+            }
+        }
+
+        if !self.shutdown {
+            eprintln!("Language server exited without a shutdown request.");
+            return Err(ConfiguratorError::BadUsage);
+        }
+
+        Ok(())
+    }
+
+    fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+
+        let id = match message.get("id") {
+            Some(id) => id,
+            None => return self.handle_notification(method, params),
+        };
+
+        let result = match method {
+            "initialize" => self.initialize(),
+            "shutdown" => {
+                self.shutdown = true;
+                Value::Null
+            }
+            "textDocument/hover" => self.hover(params),
+            "textDocument/definition" => self.definition(params),
+            "textDocument/completion" => self.completion(params),
+            _ => {
+                return vec![json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": {
+                        "code": METHOD_NOT_FOUND_ERROR_CODE,
+                        "message": format!("unknown method {}", method),
+                    },
+                })]
+            }
+        };
+
+        vec![json!({ "jsonrpc": "2.0", "id": id, "result": result })]
+    }
+
+    fn handle_notification(&mut self, method: &str, params: &Value) -> Vec<Value> {
+        let uri = params["textDocument"]["uri"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+
+        match method {
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.documents.insert(uri.clone(), text.to_string());
+            }
+            "textDocument/didChange" => {
+                let changes = params["contentChanges"].as_array();
+
+                if let Some(text) = changes
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change["text"].as_str())
+                {
+                    self.documents.insert(uri.clone(), text.to_string());
+                }
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+            }
+            _ => return vec![],
+        }
+
+        vec![self.publish_diagnostics(&uri)]
+    }
+
+    fn initialize(&self) -> Value {
+        json!({
+            "capabilities": {
+                "textDocumentSync": FULL_TEXT_DOCUMENT_SYNC,
+                "hoverProvider": true,
+                "definitionProvider": true,
+                "completionProvider": {},
+            },
+            "serverInfo": {
+                "name": "ross_language_server",
+                "version": env!("CARGO_PKG_VERSION"),
+            },
+        })
+    }
+
+    fn publish_diagnostics(&self, uri: &str) -> Value {
+        let diagnostics: Vec<Value> = match self.preprocess(uri) {
+            Some((path, source)) => {
+                let mut diagnostics = source.diagnostics.clone();
+
+                if diagnostics.is_empty() && Parser::parse(&source.text).is_err() {
+                    diagnostics = parse_diagnostics(&source);
+                }
+
+                diagnostics
+                    .iter()
+                    .map(|diagnostic| lsp_diagnostic(&path, diagnostic))
+                    .collect()
+            }
+            None => vec![],
+        };
+
+        json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": { "uri": uri, "diagnostics": diagnostics },
+        })
+    }
+
+    fn hover(&self, params: &Value) -> Value {
+        let (path, source, line, column, word) = match self.word_at(params) {
+            Some(word) => word,
+            None => return Value::Null,
+        };
+        let file = path.to_string_lossy();
+
+        let description = if let Some(event) = EventType::all()
+            .into_iter()
+            .find(|event| event.constant_name() == word)
+        {
+            format!("`{}`: {} event code {:#06x}", word, event, event.code())
+        } else if let Some(definition) = find_definition(&source, &word) {
+            match definition.kind {
+                DefinitionKind::State(index) => format!(
+                    "`{}`: state variable {} (initial value `{}`)",
+                    word, index, definition.value
+                ),
+                DefinitionKind::Constant => {
+                    self.describe_value(&word, &self.resolve(&source, &definition.value))
+                }
+            }
+        } else if let Some(value) = source.substituted_word(&file, line + 1, column) {
+            self.describe_value(&word, &value)
+        } else if parse_number(&word).is_some() {
+            self.describe_value(&word, &word)
+        } else {
+            return Value::Null;
+        };
+
+        json!({ "contents": { "kind": "markdown", "value": description } })
+    }
+
+    fn definition(&self, params: &Value) -> Value {
+        let (_, source, _, _, word) = match self.word_at(params) {
+            Some(word) => word,
+            None => return Value::Null,
+        };
+
+        match find_definition(&source, &word) {
+            Some(definition) => json!({
+                "uri": path_to_uri(Path::new(&definition.file)),
+                "range": range(
+                    &definition.line_text,
+                    definition.line - 1,
+                    definition.column,
+                    definition.name.chars().count(),
+                ),
+            }),
+            None => Value::Null,
+        }
+    }
+
+    fn completion(&self, params: &Value) -> Value {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+
+        let mut items: Vec<Value> = EventType::all()
+            .into_iter()
+            .map(|event| {
+                json!({
+                    "label": event.constant_name(),
+                    "kind": ENUM_MEMBER_COMPLETION_KIND,
+                    "detail": format!("{} event code {:#06x}", event, event.code()),
+                })
+            })
+            .collect();
+
+        if let Some((_, source)) = self.preprocess(uri) {
+            for definition in scan_definitions(&source) {
+                let (kind, detail) = match definition.kind {
+                    DefinitionKind::State(index) => (
+                        VARIABLE_COMPLETION_KIND,
+                        format!("state variable {}", index),
+                    ),
+                    DefinitionKind::Constant => {
+                        (CONSTANT_COMPLETION_KIND, format!("= {}", definition.value))
+                    }
+                };
+
+                items.push(json!({
+                    "label": definition.name,
+                    "kind": kind,
+                    "detail": detail,
+                }));
+            }
+        }
+
+        Value::Array(items)
+    }
+
+    fn preprocess(&self, uri: &str) -> Option<(PathBuf, PreprocessedSource)> {
+        let text = self.documents.get(uri)?;
+        let path = uri_to_path(uri)?;
+        let source = preprocess_text(&path, text, &self.definitions).ok()?;
+
+        Some((path, source))
+    }
+
+    fn word_at(
+        &self,
+        params: &Value,
+    ) -> Option<(PathBuf, PreprocessedSource, usize, usize, String)> {
+        let uri = params["textDocument"]["uri"].as_str()?;
+        let line = params["position"]["line"].as_u64()? as usize;
+        let character = params["position"]["character"].as_u64()? as usize;
+
+        let text = self.documents.get(uri)?.lines().nth(line)?;
+        let (column, word) = word_at(text, char_column(text, character))?;
+        let (path, source) = self.preprocess(uri)?;
+
+        Some((path, source, line, column, word))
+    }
+
+    fn resolve(&self, source: &PreprocessedSource, value: &str) -> String {
+        match find_definition(source, value) {
+            Some(definition) if definition.kind == DefinitionKind::Constant => definition.value,
+            _ => self
+                .definitions
+                .aliases
+                .get(value)
+                .cloned()
+                .unwrap_or_else(|| value.to_string()),
+        }
+    }
+
+    fn describe_value(&self, word: &str, value: &str) -> String {
+        let mut description = if word == value {
+            format!("`{}`", word)
+        } else {
+            format!("`{}` = `{}`", word, value)
+        };
+
+        let address = parse_number(value).filter(|number| *number <= u16::MAX as u64);
+
+        if let Some(device) = address.and_then(|address| self.inventory.device(address as u16)) {
+            description += &format!("\n\nDevice (address: {:#06x})", device.address);
+
+            if let Some(name) = &device.name {
+                description += &format!(" \"{}\"", name);
+            }
+
+            if let Some(profile) = &device.profile {
+                description += &format!(", profile: {}", profile);
+            }
+        }
+
+        description
+    }
+}
+
+impl Default for LanguageServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn read_message(reader: &mut impl BufRead) -> Result<Option<Vec<u8>>, ConfiguratorError> {
+    loop {
+        let mut content_length = None;
+
+        loop {
+            let mut header = String::new();
+
+            if reader
+                .read_line(&mut header)
+                .map_err(ConfiguratorError::IOError)?
+                == 0
+            {
+                return Ok(None);
+            }
+
+            let header = header.trim_end();
+
+            if header.is_empty() {
+                break;
+            }
+
+            if let Some(length) = header.strip_prefix("Content-Length:") {
+                content_length = length.trim().parse::<usize>().ok();
+            }
+        }
+
+        let content_length = match content_length {
+            Some(content_length) => content_length,
+            None => {
+                eprintln!("Ignoring language server message without a Content-Length header.");
+                continue;
+            }
+        };
+
+        let mut body = vec![0; content_length];
+        reader
+            .read_exact(&mut body)
+            .map_err(ConfiguratorError::IOError)?;
+
+        return Ok(Some(body));
+    }
+}
+
+fn write_message(writer: &mut impl Write, message: &Value) -> Result<(), ConfiguratorError> {
+    let body = serde_json::to_string(message).map_err(ConfiguratorError::JsonError)?;
+
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)
+        .and_then(|_| writer.flush())
+        .map_err(ConfiguratorError::IOError)
+}
+
+fn lsp_diagnostic(path: &Path, diagnostic: &Diagnostic) -> Value {
+    let severity = match diagnostic.severity {
+        Severity::Error => 1,
+        Severity::Warning => 2,
+    };
+
+    let mut message = diagnostic.message.clone();
+
+    if let Some(hint) = &diagnostic.hint {
+        message += &format!("\nhint: {}", hint);
+    }
+
+    let range = if Path::new(&diagnostic.file) == path {
+        range(
+            &diagnostic.source_line,
+            diagnostic.line - 1,
+            diagnostic.column - 1,
+            diagnostic.length,
+        )
+    } else {
+        message = format!(
+            "{}:{}:{}: {}",
+            diagnostic.file, diagnostic.line, diagnostic.column, message
+        );
+        range("", 0, 0, 0)
+    };
+
+    json!({
+        "range": range,
+        "severity": severity,
+        "source": "ross",
+        "message": message,
+    })
+}
+
+fn range(text: &str, line: usize, column: usize, length: usize) -> Value {
+    json!({
+        "start": { "line": line, "character": utf16_column(text, column) },
+        "end": { "line": line, "character": utf16_column(text, column + length) },
+    })
+}
+
+fn utf16_column(text: &str, column: usize) -> usize {
+    let chars = text.chars().count();
+
+    text.chars()
+        .take(column)
+        .map(char::len_utf16)
+        .sum::<usize>()
+        + column.saturating_sub(chars)
+}
+
+fn char_column(text: &str, utf16_column: usize) -> usize {
+    let mut units = 0;
+
+    for (column, c) in text.chars().enumerate() {
+        if units >= utf16_column {
+            return column;
+        }
+
+        units += c.len_utf16();
+    }
+
+    text.chars().count() + utf16_column.saturating_sub(units)
+}
+
+fn scan_definitions(source: &PreprocessedSource) -> Vec<Definition> {
+    let mut definitions = vec![];
+    let mut state_index = 0;
+
+    for source_line in source.lines.iter() {
+        let code = source_line.text.split("//").next().unwrap_or_default();
+        let mut statement_start = 0;
+
+        for statement in code.split(';') {
+            let trimmed = statement.trim_start();
+            let indent = statement_start + statement.len() - trimmed.len();
+            statement_start += statement.len() + 1;
+
+            for keyword in [LET_KEYWORD, CONST_KEYWORD, CONST_DIRECTIVE_ALIAS].iter() {
+                let (name, value) = match directive_argument(trimmed, keyword)
+                    .and_then(|argument| argument.split_once('='))
+                {
+                    Some((name, value)) => (name.trim(), value.trim()),
+                    None => continue,
+                };
+
+                let kind = if *keyword == LET_KEYWORD {
+                    state_index += 1;
+                    DefinitionKind::State(state_index - 1)
+                } else {
+                    DefinitionKind::Constant
+                };
+
+                let column = trimmed[keyword.len()..]
+                    .find(name)
+                    .map(|offset| indent + keyword.len() + offset)
+                    .unwrap_or(indent);
+
+                definitions.push(Definition {
+                    name: name.to_string(),
+                    kind,
+                    value: value.to_string(),
+                    file: source_line.file.clone(),
+                    line: source_line.line,
+                    column: code[..column].chars().count(),
+                    line_text: source_line.text.clone(),
+                });
+            }
+        }
+    }
+
+    definitions
+}
+
+fn find_definition(source: &PreprocessedSource, name: &str) -> Option<Definition> {
+    scan_definitions(source)
+        .into_iter()
+        .rev()
+        .find(|definition| definition.name == name)
+}
+
+fn word_at(line: &str, character: usize) -> Option<(usize, String)> {
+    let chars: Vec<char> = line.chars().collect();
+    let is_word_char = |c: char| c.is_alphanumeric() || c == '_' || c == '~';
+
+    let mut start = character.min(chars.len());
+    while start > 0 && is_word_char(chars[start - 1]) {
+        start -= 1;
+    }
+
+    let mut end = character.min(chars.len());
+    while end < chars.len() && is_word_char(chars[end]) {
+        end += 1;
+    }
+
+    if start == end {
+        return None;
+    }
+
+    Some((start, chars[start..end].iter().collect()))
+}
+
+fn parse_number(value: &str) -> Option<u64> {
+    let number = value.split('~').next().unwrap_or_default();
+
+    parse_int::parse::<u64>(number).ok()
+}
+
+fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let path = uri.strip_prefix(FILE_URI_PREFIX)?;
+    let mut bytes = vec![];
+    let mut chars = path.bytes();
+
+    while let Some(byte) = chars.next() {
+        if byte == b'%' {
+            let hex: Vec<u8> = chars.by_ref().take(2).collect();
+            let decoded = std::str::from_utf8(&hex)
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())?;
+            bytes.push(decoded);
+        } else {
+            bytes.push(byte);
+        }
+    }
+
+    String::from_utf8(bytes).ok().map(PathBuf::from)
+}
+
+fn path_to_uri(path: &Path) -> String {
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let mut uri = FILE_URI_PREFIX.to_string();
+
+    for byte in path.to_string_lossy().bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-_.~".contains(&byte) {
+            uri.push(byte as char);
+        } else {
+            uri += &format!("%{:02X}", byte);
+        }
+    }
+
+    uri
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn columns_convert_between_chars_and_utf16() {
+        let text = "// \u{1f600} light";
+
+        assert_eq!(utf16_column(text, 4), 5);
+        assert_eq!(char_column(text, 5), 4);
+        assert_eq!(utf16_column(text, 3), char_column(text, 3));
+        assert_eq!(char_column(text, 100), 100 - 1);
+    }
+
+    #[test]
+    fn words_are_found_around_the_cursor() {
+        assert_eq!(
+            word_at("send LIGHT to 0x0010~u16;", 7),
+            Some((5, "LIGHT".to_string()))
+        );
+        assert_eq!(
+            word_at("send LIGHT to 0x0010~u16;", 16),
+            Some((14, "0x0010~u16".to_string()))
+        );
+        assert_eq!(word_at("send ;", 5), None);
+    }
+
+    #[test]
+    fn uris_round_trip_through_paths() {
+        assert_eq!(
+            uri_to_path("file:///tmp/my%20config.ross"),
+            Some(PathBuf::from("/tmp/my config.ross"))
+        );
+        assert_eq!(
+            path_to_uri(Path::new("/nonexistent/my config.ross")),
+            "file:///nonexistent/my%20config.ross"
+        );
+        assert_eq!(uri_to_path("untitled:1"), None);
+    }
+
+    #[test]
+    fn messages_are_framed_by_content_length() {
+        let mut input = "Content-Length: 2\r\n\r\n{}Content-Length: 1\r\n\r\n{".as_bytes();
+
+        assert_eq!(read_message(&mut input).unwrap(), Some(b"{}".to_vec()));
+        assert_eq!(read_message(&mut input).unwrap(), Some(b"{".to_vec()));
+        assert_eq!(read_message(&mut input).unwrap(), None);
+    }
+
+    #[test]
+    fn messages_without_content_length_are_skipped() {
+        let mut input = "Content-Type: x\r\n\r\nContent-Length: 2\r\n\r\n{}".as_bytes();
+
+        assert_eq!(read_message(&mut input).unwrap(), Some(b"{}".to_vec()));
+        assert_eq!(read_message(&mut input).unwrap(), None);
+    }
+
+    #[test]
+    fn every_statement_on_a_line_is_scanned() {
+        let source = preprocess_text(
+            Path::new("/nonexistent/config.ross"),
+            "let a = 1~u8; let b = 2~u8;\nconst c = 3~u8; let d = false; // let e = 0;\n",
+            &Definitions::default(),
+        )
+        .unwrap();
+
+        let definitions = scan_definitions(&source)
+            .into_iter()
+            .map(|definition| {
+                (
+                    definition.name,
+                    definition.kind,
+                    definition.value,
+                    definition.column,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            definitions,
+            vec![
+                (
+                    "a".to_string(),
+                    DefinitionKind::State(0),
+                    "1~u8".to_string(),
+                    4
+                ),
+                (
+                    "b".to_string(),
+                    DefinitionKind::State(1),
+                    "2~u8".to_string(),
+                    18
+                ),
+                (
+                    "c".to_string(),
+                    DefinitionKind::Constant,
+                    "3~u8".to_string(),
+                    6
+                ),
+                (
+                    "d".to_string(),
+                    DefinitionKind::State(2),
+                    "false".to_string(),
+                    20
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn unsaved_documents_get_diagnostics() {
+        let mut server = LanguageServer {
+            documents: BTreeMap::new(),
+            inventory: Inventory::default(),
+            definitions: Definitions::default(),
+            shutdown: false,
+        };
+
+        let responses = server.handle(&json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didOpen",
+            "params": {
+                "textDocument": {
+                    "uri": "file:///nonexistent/unsaved.ross",
+                    "text": "let light = ;\n",
+                },
+            },
+        }));
+
+        assert_eq!(
+            responses[0]["params"]["diagnostics"]
+                .as_array()
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+}
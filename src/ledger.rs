@@ -0,0 +1,71 @@
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::ross_configurator::*;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    pub board: String,
+    pub version: Option<Version>,
+    pub flashed_at: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Ledger {
+    pub devices: BTreeMap<u16, LedgerEntry>,
+}
+
+impl Ledger {
+    pub fn default_path() -> PathBuf {
+        data_directory().join(LEDGER_FILE_NAME)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, ConfiguratorError> {
+        match fs::read_to_string(path) {
+            Ok(string) => serde_json::from_str(&string).map_err(ConfiguratorError::JsonError),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(ConfiguratorError::IOError(err)),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), ConfiguratorError> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(ConfiguratorError::IOError)?;
+        }
+
+        let string = serde_json::to_string_pretty(self).map_err(ConfiguratorError::JsonError)?;
+        let temp_path = path.with_extension("json.tmp");
+
+        fs::write(&temp_path, string).map_err(ConfiguratorError::IOError)?;
+        fs::rename(&temp_path, path).map_err(ConfiguratorError::IOError)
+    }
+
+    pub fn get(&self, address: u16) -> Option<&LedgerEntry> {
+        self.devices.get(&address)
+    }
+
+    pub fn record(&mut self, address: u16, board: String, version: Option<Version>) {
+        let flashed_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
+
+        self.devices.insert(
+            address,
+            LedgerEntry {
+                board,
+                version,
+                flashed_at,
+            },
+        );
+    }
+
+    pub fn forget(&mut self, address: u16) {
+        self.devices.remove(&address);
+    }
+}
@@ -1,8 +1,35 @@
+pub mod batch;
+pub mod bundle;
+pub mod cancellation;
+pub mod check;
+pub mod compile;
+pub mod config_diff;
+pub mod config_file;
+pub mod config_graph;
+pub mod config_model;
+pub mod config_size;
+pub mod diagnostics;
 pub mod event_type;
+pub mod firmware;
+pub mod firmware_repository;
+pub mod fmt;
 pub mod get_devices;
 pub mod get_programmer;
+pub mod inspect;
+pub mod inventory;
+pub mod language_server;
+pub mod ledger;
+pub mod lint;
+pub mod preprocessor;
+pub mod rollout;
 pub mod ross_configurator;
 pub mod send_event;
 pub mod set_device_address;
+pub mod simulate;
+pub mod stats;
+pub mod template;
+pub mod transfer;
 pub mod upgrade_config;
 pub mod upgrade_firmware;
+pub mod verify;
+pub mod watch;
@@ -0,0 +1,417 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use ross_config::config::Config;
+use ross_config::creator::Creator;
+use ross_config::extractor::*;
+use ross_config::filter::*;
+use ross_config::matcher::Matcher;
+use ross_config::producer::*;
+use ross_protocol::protocol::BROADCAST_ADDRESS;
+
+use crate::config_model::{CreatorModel, MatcherModel};
+use crate::diagnostics::Severity;
+use crate::inventory::Inventory;
+
+pub const UNUSED_STATE_LINT: &str = "unused_state";
+pub const NEVER_MATCHES_LINT: &str = "never_matches";
+pub const DUPLICATE_HANDLER_LINT: &str = "duplicate_handler";
+pub const CONFLICTING_HANDLER_LINT: &str = "conflicting_handler";
+pub const UNKNOWN_ADDRESS_LINT: &str = "unknown_address";
+
+#[derive(Debug, Clone)]
+pub struct Lint {
+    pub severity: Severity,
+    pub name: &'static str,
+    pub message: String,
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}[{}]: {}", self.severity, self.name, self.message)
+    }
+}
+
+pub fn lint_config(config: &Config, inventory: &Inventory) -> Vec<Lint> {
+    let mut lints = vec![];
+
+    lint_unused_state(config, &mut lints);
+    lint_never_matching(config, &mut lints);
+    lint_duplicate_handlers(config, &mut lints);
+
+    if !inventory.devices.is_empty() {
+        lint_unknown_addresses(config, inventory, &mut lints);
+    }
+
+    lints
+}
+
+fn lint_unused_state(config: &Config, lints: &mut Vec<Lint>) {
+    let mut used = BTreeSet::new();
+
+    for event_processor in config.event_processors.iter() {
+        matcher_state_indexes(&event_processor.matcher, &mut used);
+
+        for creator in event_processor.creators.iter() {
+            if let Some(matcher) = &creator.matcher {
+                matcher_state_indexes(matcher, &mut used);
+            }
+
+            let data = creator.producer.serialize();
+
+            let state_index = match creator.producer.get_code() {
+                BCM_CHANGE_BRIGHTNESS_STATE_PRODUCER_CODE => read_u32(&data, 3),
+                BCM_ANIMATE_BRIGHTNESS_STATE_PRODUCER_CODE => read_u32(&data, 7),
+                _ => None,
+            };
+
+            used.extend(state_index);
+        }
+    }
+
+    for (index, value) in config.initial_state.iter() {
+        if !used.contains(index) {
+            lints.push(Lint {
+                severity: Severity::Warning,
+                name: UNUSED_STATE_LINT,
+                message: format!(
+                    "state variable {} ({:?}) is never read or written",
+                    index, value
+                ),
+            });
+        }
+    }
+}
+
+fn lint_never_matching(config: &Config, lints: &mut Vec<Lint>) {
+    for (i, event_processor) in config.event_processors.iter().enumerate() {
+        let matcher = MatcherModel::from_matcher(&event_processor.matcher);
+
+        if let Some(reason) = type_mismatch(&event_processor.matcher) {
+            lints.push(Lint {
+                severity: Severity::Error,
+                name: NEVER_MATCHES_LINT,
+                message: format!(
+                    "event processor #{} (match {}) can never match: {}",
+                    i, matcher, reason
+                ),
+            });
+        } else if never_matches(&event_processor.matcher) {
+            lints.push(Lint {
+                severity: Severity::Warning,
+                name: NEVER_MATCHES_LINT,
+                message: format!(
+                    "event processor #{} (match {}) can never match: it requires one value to equal different constants",
+                    i, matcher
+                ),
+            });
+        }
+    }
+}
+
+fn lint_duplicate_handlers(config: &Config, lints: &mut Vec<Lint>) {
+    let matchers: Vec<MatcherModel> = config
+        .event_processors
+        .iter()
+        .map(|event_processor| MatcherModel::from_matcher(&event_processor.matcher))
+        .collect();
+
+    for (i, event_processor) in config.event_processors.iter().enumerate() {
+        for j in 0..i {
+            if matchers[i] != matchers[j] {
+                continue;
+            }
+
+            let creators: Vec<CreatorModel> = event_processor
+                .creators
+                .iter()
+                .map(CreatorModel::from_creator)
+                .collect();
+            let other_creators: Vec<CreatorModel> = config.event_processors[j]
+                .creators
+                .iter()
+                .map(CreatorModel::from_creator)
+                .collect();
+
+            if creators == other_creators {
+                lints.push(Lint {
+                    severity: Severity::Warning,
+                    name: DUPLICATE_HANDLER_LINT,
+                    message: format!(
+                        "event processor #{} duplicates event processor #{} (match {})",
+                        i, j, matchers[i]
+                    ),
+                });
+            } else if conflicts(
+                &event_processor.creators,
+                &config.event_processors[j].creators,
+            ) {
+                lints.push(Lint {
+                    severity: Severity::Warning,
+                    name: CONFLICTING_HANDLER_LINT,
+                    message: format!(
+                        "event processors #{} and #{} handle the same event (match {}) with conflicting actions",
+                        j, i, matchers[i]
+                    ),
+                });
+            }
+
+            break;
+        }
+    }
+}
+
+fn lint_unknown_addresses(config: &Config, inventory: &Inventory, lints: &mut Vec<Lint>) {
+    let mut addresses = BTreeMap::new();
+
+    for (i, event_processor) in config.event_processors.iter().enumerate() {
+        for creator in event_processor.creators.iter() {
+            if let Some(address) =
+                target_address(creator.producer.get_code(), &creator.producer.serialize())
+            {
+                addresses.entry(address).or_insert(i);
+            }
+        }
+    }
+
+    for (address, i) in addresses.into_iter() {
+        if address != BROADCAST_ADDRESS && inventory.device(address).is_none() {
+            lints.push(Lint {
+                severity: Severity::Warning,
+                name: UNKNOWN_ADDRESS_LINT,
+                message: format!(
+                    "event processor #{} sends to address {:#06x}, which is not in the device inventory",
+                    i, address
+                ),
+            });
+        }
+    }
+}
+
+fn matcher_state_indexes(matcher: &Matcher, used: &mut BTreeSet<u32>) {
+    match matcher {
+        Matcher::Single { filter, .. } => {
+            let data = filter.serialize();
+
+            match filter.get_code() {
+                VALUE_EQUAL_TO_CONST_FILTER_CODE | TIME_MATCHES_CRON_EXPRESSION_FILTER_CODE => {}
+                SET_STATE_TO_STATE_FILTER_CODE | STATE_EQUAL_TO_STATE_FILTER_CODE => {
+                    used.extend(read_u32(&data, 0));
+                    used.extend(read_u32(&data, 4));
+                }
+                _ => used.extend(read_u32(&data, 0)),
+            }
+        }
+        Matcher::Not(matcher) => matcher_state_indexes(matcher, used),
+        Matcher::Or(left, right) | Matcher::And(left, right) => {
+            matcher_state_indexes(left, used);
+            matcher_state_indexes(right, used);
+        }
+    }
+}
+
+fn type_mismatch(matcher: &Matcher) -> Option<String> {
+    match matcher {
+        Matcher::Single { extractor, filter } => {
+            if filter.get_code() != VALUE_EQUAL_TO_CONST_FILTER_CODE {
+                return None;
+            }
+
+            let extracted_type = match extractor.get_code() {
+                EVENT_CODE_EXTRACTOR_CODE
+                | EVENT_PRODUCER_ADDRESS_EXTRACTOR_CODE
+                | MESSAGE_CODE_EXTRACTOR_CODE => "u16",
+                BUTTON_INDEX_EXTRACTOR_CODE => "u8",
+                NONE_EXTRACTOR_CODE => "nothing",
+                PACKET_EXTRACTOR_CODE => "a packet",
+                _ => return None,
+            };
+
+            let compared_type = match filter.serialize().first() {
+                Some(0x00) => "u8",
+                Some(0x01) => "u16",
+                Some(0x02) => "u32",
+                Some(0x03) => "bool",
+                _ => "a color",
+            };
+
+            if extracted_type == compared_type {
+                None
+            } else {
+                Some(format!(
+                    "{:?} extracts {} but it is compared to {}",
+                    extractor, extracted_type, compared_type
+                ))
+            }
+        }
+        Matcher::Not(_) => None,
+        Matcher::Or(left, right) => match (type_mismatch(left), type_mismatch(right)) {
+            (Some(reason), Some(_)) => Some(reason),
+            _ => None,
+        },
+        Matcher::And(left, right) => type_mismatch(left).or_else(|| type_mismatch(right)),
+    }
+}
+
+fn never_matches(matcher: &Matcher) -> bool {
+    match matcher {
+        Matcher::Or(left, right) => never_matches(left) && never_matches(right),
+        Matcher::And(_, _) => {
+            let mut required = BTreeMap::new();
+
+            !required_values(matcher, &mut required)
+        }
+        _ => false,
+    }
+}
+
+fn required_values(matcher: &Matcher, required: &mut BTreeMap<(u16, Vec<u8>), Vec<u8>>) -> bool {
+    match matcher {
+        Matcher::Single { extractor, filter } => {
+            if filter.get_code() != VALUE_EQUAL_TO_CONST_FILTER_CODE {
+                return true;
+            }
+
+            let value = filter.serialize();
+
+            match required.insert((extractor.get_code(), extractor.serialize()), value.clone()) {
+                Some(previous) => previous == value,
+                None => true,
+            }
+        }
+        Matcher::And(left, right) => {
+            required_values(left, required) && required_values(right, required)
+        }
+        matcher => !never_matches(matcher),
+    }
+}
+
+fn conflicts(creators: &[Creator], other_creators: &[Creator]) -> bool {
+    creators.iter().any(|creator| {
+        let code = creator.producer.get_code();
+        let data = creator.producer.serialize();
+
+        other_creators.iter().any(|other| {
+            let other_data = other.producer.serialize();
+
+            other.producer.get_code() == code
+                && target_prefix_length(code).is_some_and(|length| {
+                    data.len() >= length
+                        && other_data.len() >= length
+                        && data[..length] == other_data[..length]
+                })
+                && data != other_data
+        })
+    })
+}
+
+fn target_prefix_length(code: u16) -> Option<usize> {
+    match code {
+        PACKET_PRODUCER_CODE => Some(2),
+        MESSAGE_PRODUCER_CODE => Some(4),
+        BCM_CHANGE_BRIGHTNESS_PRODUCER_CODE
+        | BCM_CHANGE_BRIGHTNESS_STATE_PRODUCER_CODE
+        | BCM_ANIMATE_BRIGHTNESS_PRODUCER_CODE
+        | BCM_ANIMATE_BRIGHTNESS_STATE_PRODUCER_CODE
+        | RELAY_SET_VALUE_PRODUCER_CODE => Some(3),
+        _ => None,
+    }
+}
+
+pub fn target_address(code: u16, data: &[u8]) -> Option<u16> {
+    target_prefix_length(code)?;
+
+    if data.len() < 2 {
+        return None;
+    }
+
+    Some(u16::from_be_bytes([data[0], data[1]]))
+}
+
+pub fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    if data.len() < offset + 4 {
+        return None;
+    }
+
+    Some(u32::from_be_bytes([
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use ross_dsl::Parser;
+
+    fn lint(text: &str) -> Vec<Lint> {
+        lint_config(&Parser::parse(text).unwrap(), &Inventory::default())
+    }
+
+    #[test]
+    fn type_mismatch_never_matches() {
+        let lints = lint(
+            "do {
+                match event 0x0002~u16;
+                match { ButtonIndexExtractor(); ValueEqualToConstFilter(0x0003~u16); }
+                fire { PacketExtractor(); PacketProducer(0x0011~u16); }
+            }",
+        );
+
+        assert_eq!(lints.len(), 1);
+        assert_eq!(lints[0].name, NEVER_MATCHES_LINT);
+        assert_eq!(lints[0].severity, Severity::Error);
+    }
+
+    #[test]
+    fn negated_type_mismatch_is_not_reported() {
+        let lints = lint(
+            "do {
+                match event 0x0002~u16;
+                match not { ButtonIndexExtractor(); ValueEqualToConstFilter(0x0003~u16); }
+                fire { PacketExtractor(); PacketProducer(0x0011~u16); }
+            }",
+        );
+
+        assert!(lints.is_empty(), "{:?}", lints);
+    }
+
+    #[test]
+    fn conflicting_constants_never_match() {
+        let lints = lint(
+            "do {
+                match event 0x0002~u16;
+                match { EventCodeExtractor(); ValueEqualToConstFilter(0x0003~u16); }
+                fire { PacketExtractor(); PacketProducer(0x0011~u16); }
+            }",
+        );
+
+        assert_eq!(lints.len(), 1);
+        assert_eq!(lints[0].name, NEVER_MATCHES_LINT);
+        assert_eq!(lints[0].severity, Severity::Warning);
+    }
+
+    #[test]
+    fn unused_state_is_reported() {
+        let lints = lint("let unused = 5~u8;");
+
+        assert_eq!(lints.len(), 1);
+        assert_eq!(lints[0].name, UNUSED_STATE_LINT);
+    }
+
+    #[test]
+    fn unknown_address_is_reported_only_with_an_inventory() {
+        let config = Parser::parse("send 0x0001~u16 from 0x0123~u16 to 0x0050~u16;").unwrap();
+        let inventory: Inventory = toml::from_str("[[devices]]\naddress = 0x0011\n").unwrap();
+
+        assert!(lint_config(&config, &Inventory::default()).is_empty());
+
+        let lints = lint_config(&config, &inventory);
+
+        assert_eq!(lints.len(), 1);
+        assert_eq!(lints[0].name, UNKNOWN_ADDRESS_LINT);
+    }
+}
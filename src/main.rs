@@ -1,21 +1,45 @@
-use clap::{clap_app, value_t};
+use clap::{clap_app, value_t, ArgMatches};
 use parse_int::parse;
-use std::fs::File;
-use std::io::{BufReader, Read};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::path::Path;
 use std::time::Duration;
 
-use ross_dsl::Parser;
 use ross_protocol::interface::serial::Serial;
 use ross_protocol::protocol::{Protocol, BROADCAST_ADDRESS};
 
+use ross_configurator::batch::{
+    all_addresses, into_result, parse_addresses, print_summary, run_for_each,
+};
+use ross_configurator::bundle::bundle;
+use ross_configurator::cancellation::CancellationToken;
+use ross_configurator::check::check;
+use ross_configurator::compile::compile;
+use ross_configurator::config_diff::config_diff;
+use ross_configurator::config_file::load_config_data;
+use ross_configurator::config_graph::{config_graph, GraphFormat};
+use ross_configurator::config_size::check_config_size;
 use ross_configurator::event_type::EventType;
+use ross_configurator::firmware::{check_firmware, Firmware};
+use ross_configurator::firmware_repository::{FirmwareRepository, VersionSelector};
+use ross_configurator::fmt::fmt;
 use ross_configurator::get_devices::get_devices;
 use ross_configurator::get_programmer::get_programmer;
+use ross_configurator::inspect::inspect;
+use ross_configurator::inventory::{ChunkSize, Inventory};
+use ross_configurator::ledger::Ledger;
+use ross_configurator::rollout::{rollout, RolloutOptions};
 use ross_configurator::ross_configurator::*;
 use ross_configurator::send_event::send_event;
 use ross_configurator::set_device_address::set_device_address;
+use ross_configurator::simulate::simulate;
+use ross_configurator::stats::write_stats_file;
+use ross_configurator::template::render_config_data;
+use ross_configurator::transfer::{TransferJournal, TransferKind, TransferOptions};
 use ross_configurator::upgrade_config::upgrade_config;
 use ross_configurator::upgrade_firmware::upgrade_firmware;
+use ross_configurator::verify::verify_device;
+use ross_configurator::watch::{config_source_files, ConfigWatcher};
 
 fn main() -> Result<(), ConfiguratorError> {
     let matches = clap_app!(ross_configurator =>
@@ -23,8 +47,11 @@ fn main() -> Result<(), ConfiguratorError> {
         (version: env!("CARGO_PKG_VERSION"))
         (author: env!("CARGO_PKG_AUTHORS"))
         (about: env!("CARGO_PKG_DESCRIPTION"))
-        (@arg DEVICE: -d --device +required +takes_value "Path of device to use")
+        (@arg DEVICE: -d --device +takes_value "Path of device to use")
         (@arg BAUDRATE: -b --baudrate +takes_value "Baudrate to use")
+        (@arg DRY_RUN: --("dry-run") "Prints the packets that would change devices' state instead of sending them")
+        (@arg ENV: --env +takes_value "Environment overlay to apply to DSL configs (e.g. lab, prod)")
+        (@arg NO_CHECKSUM: --("no-checksum") "Uses compiled config blobs that have no .sha256 checksum file")
         (@subcommand get_programmer =>
             (about: "Gets connected programmer's information")
         )
@@ -32,14 +59,43 @@ fn main() -> Result<(), ConfiguratorError> {
             (about: "Gets connected devices' information")
         )
         (@subcommand upgrade_firmware =>
-            (about: "Upgrades a specific device's firmware")
-            (@arg FIRMWARE: -f --firmware +required +takes_value "Path of the firmware to use")
-            (@arg ADDRESS: -a --address +required +takes_value "Recipient device address")
+            (about: "Upgrades firmware of specific devices")
+            (@group firmware_source +required =>
+                (@arg FIRMWARE: -f --firmware +takes_value "Path of the firmware to use")
+                (@arg VERSION: --version +takes_value "Version of the firmware to use from the firmware repository (e.g. latest, latest-pre, 1.4.2, 1.4.x)")
+            )
+            (@group targets +required =>
+                (@arg ADDRESS: -a --address ... +takes_value "Recipient device addresses or address ranges (e.g. 0x0010-0x001f)")
+                (@arg ALL: --all "Targets all discovered devices")
+                (@arg RESUME: --resume "Targets devices left mid-upgrade by an interrupted transfer")
+            )
+            (@arg BOARD: --board +takes_value "Board type of the recipient device")
+            (@arg ALLOW_DOWNGRADE: --("allow-downgrade") "Allows flashing an older firmware version")
+            (@arg FORCE: --force "Allows flashing firmware without version metadata, skipping compatibility checks")
+            (@arg CHUNK_SIZE: --("chunk-size") +takes_value "Size of transferred data chunks in bytes, or \"adaptive\"")
+            (@arg STATS_FILE: --("stats-file") +takes_value "Path of a JSON file to write transfer statistics to")
+            (@arg VERIFY: --verify "Verifies that upgraded devices answer again at their address")
+            (@arg VERIFY_DELAY: --("verify-delay") +takes_value requires[VERIFY] "Time in milliseconds to wait before verifying upgraded devices")
+            (@arg ROLLOUT: --rollout conflicts_with[VERIFY] "Upgrades a canary device first and the rest in batches, halting on failures; waits for upgraded devices to reappear instead of --verify")
+            (@arg CANARY: --canary +takes_value requires[ROLLOUT] "Address of the canary device (defaults to the first target)")
+            (@arg BATCH_SIZE: --("batch-size") +takes_value requires[ROLLOUT] "Number of devices upgraded per rollout batch")
+            (@arg MAX_FAILURES: --("max-failures") +takes_value requires[ROLLOUT] "Number of failed devices tolerated before the rollout halts")
+            (@arg REDISCOVERY_TIMEOUT: --("rediscovery-timeout") +takes_value requires[ROLLOUT] "Time in milliseconds to wait for upgraded devices to reappear")
         )
         (@subcommand upgrade_config =>
-            (about: "Upgrades a specific device's config")
-            (@arg CONFIG: -c --config +required +takes_value "Path of the config to use")
-            (@arg ADDRESS: -a --address +required +takes_value "Recipient device address")
+            (about: "Upgrades config of specific devices")
+            (@arg CONFIG: -c --config +required +takes_value "Path of the config or compiled .bin config blob to use")
+            (@group targets +required =>
+                (@arg ADDRESS: -a --address ... +takes_value "Recipient device addresses or address ranges (e.g. 0x0010-0x001f)")
+                (@arg ALL: --all "Targets all discovered devices")
+                (@arg RESUME: --resume "Targets devices left mid-upgrade by an interrupted transfer")
+                (@arg PARAMS: --params +takes_value "Path of a TOML or YAML (.yaml, .yml) table of per-device template parameters; targets its devices")
+            )
+            (@arg CHUNK_SIZE: --("chunk-size") +takes_value "Size of transferred data chunks in bytes, or \"adaptive\"")
+            (@arg STATS_FILE: --("stats-file") +takes_value "Path of a JSON file to write transfer statistics to")
+            (@arg VERIFY: --verify "Verifies that upgraded devices answer again at their address")
+            (@arg VERIFY_DELAY: --("verify-delay") +takes_value requires[VERIFY] "Time in milliseconds to wait before verifying upgraded devices")
+            (@arg WATCH: --watch conflicts_with[RESUME] "Watches the config and its includes, uploading again on every change")
         )
         (@subcommand set_device_address =>
             (about: "Sets a specific device's address")
@@ -51,10 +107,152 @@ fn main() -> Result<(), ConfiguratorError> {
             (@arg EVENT: -e --event +required +takes_value "Type of the event")
             (@arg DATA: -d --data ... +required +takes_value "Data of the event")
         )
+        (@subcommand compile =>
+            (about: "Compiles a config into the binary blob sent to devices, without a device")
+            (@arg CONFIG: -c --config +required +takes_value "Path of the config to compile")
+            (@arg OUTPUT: -o --output +required +takes_value "Path of the binary config to write, ending in .bin")
+        )
+        (@subcommand bundle =>
+            (about: "Packages a firmware image with a manifest for \"firmware import\", without a device")
+            (@arg FIRMWARE: -f --firmware +required +takes_value "Path of the firmware image to bundle")
+            (@arg OUTPUT: -o --output +required +takes_value "Path of the directory to write the image and manifest to")
+            (@arg BOARD: --board +takes_value "Board type of the firmware, required if the image has no header")
+            (@arg VERSION: --version +takes_value "Version of the firmware, required if the image has no header")
+        )
+        (@subcommand inspect =>
+            (about: "Decodes a compiled config blob or config, without a device")
+            (@arg CONFIG: +required "Path of the compiled config blob or config to inspect")
+            (@arg JSON: --json "Prints the decoded config as JSON")
+        )
+        (@subcommand fmt =>
+            (about: "Formats configs in the canonical style, without a device")
+            (@arg CONFIG: ... +required "Paths of the configs to format")
+            (@arg CHECK: --check "Fails if a config is not formatted instead of formatting it")
+        )
+        (@subcommand check =>
+            (about: "Checks and lints a config, without a device")
+            (@arg CONFIG: -c --config +required +takes_value "Path of the config to check")
+            (@arg DENY: --deny +takes_value possible_value[warnings] "Fails the check on findings of this severity")
+            (@arg PARAMS: --params +takes_value "Path of a TOML or YAML (.yaml, .yml) table of per-device template parameters to check every variant of")
+        )
+        (@subcommand simulate =>
+            (about: "Runs a config against a script of input events, without a device")
+            (@arg CONFIG: -c --config +required +takes_value "Path of the config or compiled config blob to simulate")
+            (@arg SCRIPT: -s --script +required +takes_value "Path of the script of events, one \"[repeat <count>] <event> [data]...\", \"time <RFC 3339 date and time>\" or \"wait <seconds>\" per line")
+            (@arg ADDRESS: -a --address +takes_value "Address of the simulated device, defaults to 0x0000")
+        )
+        (@subcommand config =>
+            (about: "Works with configs, without a device")
+            (@setting SubcommandRequiredElseHelp)
+            (@subcommand diff =>
+                (about: "Shows structural differences between two configs or compiled config blobs")
+                (@arg OLD: +required "Path of the old config")
+                (@arg NEW: +required "Path of the new config")
+            )
+            (@subcommand graph =>
+                (about: "Prints a diagram of which input events drive which state changes and output events")
+                (@arg CONFIG: +required "Path of the config or compiled config blob to draw")
+                (@arg FORMAT: --format +takes_value possible_value[dot mermaid] "Diagram format to print, defaults to dot")
+            )
+        )
+        (@subcommand firmware =>
+            (about: "Manages the local firmware repository")
+            (@setting SubcommandRequiredElseHelp)
+            (@subcommand list =>
+                (about: "Lists imported firmware releases")
+                (@arg BOARD: --board +takes_value "Board type to list releases for")
+            )
+            (@subcommand import =>
+                (about: "Imports a firmware image or bundle manifest")
+                (@arg FIRMWARE: +required "Path of the firmware to import")
+            )
+            (@subcommand prune =>
+                (about: "Removes all but the newest firmware releases of each board type")
+                (@arg BOARD: --board +takes_value "Board type to prune releases for")
+                (@arg KEEP: -k --keep +takes_value "Number of releases to keep per board type")
+            )
+        )
     )
     .get_matches();
 
-    let device = matches.value_of("DEVICE").unwrap();
+    let env = matches.value_of("ENV");
+    let require_checksum = !matches.is_present("NO_CHECKSUM");
+
+    match matches.subcommand() {
+        ("firmware", Some(sub_matches)) => return manage_firmware(sub_matches),
+        ("compile", Some(sub_matches)) => {
+            return compile(
+                Path::new(sub_matches.value_of("CONFIG").unwrap()),
+                Path::new(sub_matches.value_of("OUTPUT").unwrap()),
+                env,
+            )
+        }
+        ("bundle", Some(sub_matches)) => {
+            bundle(
+                Path::new(sub_matches.value_of("FIRMWARE").unwrap()),
+                Path::new(sub_matches.value_of("OUTPUT").unwrap()),
+                sub_matches.value_of("BOARD"),
+                sub_matches.value_of("VERSION"),
+            )?;
+
+            return Ok(());
+        }
+        ("inspect", Some(sub_matches)) => {
+            return inspect(
+                Path::new(sub_matches.value_of("CONFIG").unwrap()),
+                sub_matches.is_present("JSON"),
+                env,
+                require_checksum,
+            )
+        }
+        ("config", Some(sub_matches)) => return manage_config(sub_matches, env, require_checksum),
+        ("fmt", Some(sub_matches)) => {
+            let paths: Vec<&Path> = sub_matches
+                .values_of("CONFIG")
+                .unwrap()
+                .map(Path::new)
+                .collect();
+
+            return fmt(&paths, sub_matches.is_present("CHECK"));
+        }
+        ("check", Some(sub_matches)) => {
+            return check(
+                Path::new(sub_matches.value_of("CONFIG").unwrap()),
+                sub_matches.value_of("PARAMS").map(Path::new),
+                env,
+                sub_matches.value_of("DENY") == Some("warnings"),
+            )
+        }
+        ("simulate", Some(sub_matches)) => {
+            let device_address = match sub_matches.value_of("ADDRESS") {
+                Some(address) => match parse::<u16>(address) {
+                    Ok(address) => address,
+                    Err(_) => {
+                        eprintln!("ADDRESS is not a number.");
+                        return Err(ConfiguratorError::BadUsage);
+                    }
+                },
+                None => 0x0000,
+            };
+
+            return simulate(
+                Path::new(sub_matches.value_of("CONFIG").unwrap()),
+                Path::new(sub_matches.value_of("SCRIPT").unwrap()),
+                device_address,
+                env,
+                require_checksum,
+            );
+        }
+        _ => {}
+    }
+
+    let device = match matches.value_of("DEVICE") {
+        Some(device) => device,
+        None => {
+            eprintln!("DEVICE is required for this command.");
+            return Err(ConfiguratorError::BadUsage);
+        }
+    };
     let baudrate = match matches.value_of("BAUDRATE") {
         Some(baudrate_str) => match parse::<u64>(baudrate_str) {
             Ok(baudrate) => baudrate,
@@ -82,6 +280,12 @@ fn main() -> Result<(), ConfiguratorError> {
         Protocol::new(BROADCAST_ADDRESS, serial)
     };
 
+    let dry_run = matches.is_present("DRY_RUN");
+
+    if dry_run {
+        println!("Dry run, no state-changing packets will be sent.");
+    }
+
     match matches.subcommand() {
         ("get_programmer", _) => {
             get_programmer(&mut protocol)?;
@@ -89,67 +293,243 @@ fn main() -> Result<(), ConfiguratorError> {
         }
         ("get_devices", _) => {
             let programmer = get_programmer(&mut protocol)?;
-            get_devices(&mut protocol, &programmer)?;
+            let devices = get_devices(&mut protocol, &programmer)?;
+            let ledger = Ledger::load(&Ledger::default_path())?;
+            let journal = TransferJournal::load(&TransferJournal::default_path())?;
+
+            for device in devices.iter() {
+                if let Some(entry) = ledger.get(device.bootloader_address) {
+                    match &entry.version {
+                        Some(version) => println!(
+                            "Device (address: {:#06x}) was last flashed with {} firmware {}",
+                            device.bootloader_address, entry.board, version
+                        ),
+                        None => println!(
+                            "Device (address: {:#06x}) was last flashed with {} firmware of unknown version",
+                            device.bootloader_address, entry.board
+                        ),
+                    }
+                }
+            }
+
+            journal.print_interrupted();
+
             Ok(())
         }
         ("upgrade_firmware", sub_matches) => {
             let sub_matches = sub_matches.unwrap();
 
-            let firmware = sub_matches.value_of("FIRMWARE").unwrap();
-            let address = match parse::<u16>(sub_matches.value_of("ADDRESS").unwrap()) {
-                Ok(address) => address,
-                Err(_) => {
-                    eprintln!("ADDRESS is not a number.");
-                    return Err(ConfiguratorError::BadUsage);
-                }
-            };
+            let resume = sub_matches.is_present("RESUME");
+            let mut journal = TransferJournal::load(&TransferJournal::default_path())?;
+            let addresses =
+                match parse_target_addresses(sub_matches, &journal, TransferKind::Firmware)? {
+                    Some(addresses) if addresses.is_empty() => {
+                        println!("No interrupted firmware transfers to resume.");
+                        return Ok(());
+                    }
+                    addresses => addresses,
+                };
+            let board = sub_matches.value_of("BOARD");
+            let allow_downgrade = sub_matches.is_present("ALLOW_DOWNGRADE");
+            let force = sub_matches.is_present("FORCE");
+            let verify_delay = parse_verify_delay(sub_matches)?;
+            let chunk_size = parse_chunk_size(sub_matches)?;
+            let inventory = Inventory::load(&Inventory::default_path())?;
+            let reports = RefCell::new(vec![]);
+            let cancellation = CancellationToken::install_signal_handler()?;
+
+            let ledger_path = Ledger::default_path();
+            let mut ledger = Ledger::load(&ledger_path)?;
 
             let programmer = get_programmer(&mut protocol)?;
             let devices = get_devices(&mut protocol, &programmer)?;
+            let addresses = addresses.unwrap_or_else(|| all_addresses(&devices));
 
-            upgrade_firmware(&mut protocol, &programmer, &devices, firmware, address)?;
+            let mut upgrade = |protocol: &mut Protocol<Serial>, address| {
+                let firmware = load_firmware(sub_matches, &ledger, address, board)?;
 
-            Ok(())
+                check_firmware(&firmware, &ledger, address, board, allow_downgrade, force)?;
+
+                if resume {
+                    journal.check_resume(address, TransferKind::Firmware, &firmware.data)?;
+                }
+
+                upgrade_firmware(
+                    protocol,
+                    &programmer,
+                    &devices,
+                    &firmware.data,
+                    address,
+                    &mut journal,
+                    &TransferOptions {
+                        chunk_size: inventory.chunk_size(address, chunk_size),
+                        cancellation: &cancellation,
+                        dry_run,
+                        reports: &reports,
+                    },
+                )?;
+
+                if dry_run {
+                    return Ok(());
+                }
+
+                if let Some(verify_delay) = verify_delay {
+                    verify_device(
+                        protocol,
+                        &programmer,
+                        &devices,
+                        address,
+                        verify_delay,
+                        &cancellation,
+                    )?;
+                }
+
+                record_firmware(&mut ledger, &firmware, address, board);
+                ledger.save(&ledger_path)
+            };
+
+            let results = if sub_matches.is_present("ROLLOUT") {
+                let options = parse_rollout_options(sub_matches, &addresses, dry_run)?;
+
+                rollout(
+                    &mut protocol,
+                    &programmer,
+                    &addresses,
+                    &options,
+                    &cancellation,
+                    upgrade,
+                )?
+            } else {
+                run_for_each(&addresses, &cancellation, |address| {
+                    upgrade(&mut protocol, address)
+                })
+            };
+
+            print_summary(&results);
+
+            if let Some(stats_file) = sub_matches.value_of("STATS_FILE") {
+                write_stats_file(Path::new(stats_file), &reports.borrow())?;
+            }
+
+            into_result(results)
         }
         ("upgrade_config", sub_matches) => {
             let sub_matches = sub_matches.unwrap();
 
             let config = sub_matches.value_of("CONFIG").unwrap();
-            let address = match parse::<u16>(sub_matches.value_of("ADDRESS").unwrap()) {
-                Ok(address) => address,
-                Err(_) => {
-                    eprintln!("ADDRESS is not a number.");
-                    return Err(ConfiguratorError::BadUsage);
-                }
-            };
+            let resume = sub_matches.is_present("RESUME");
+            let mut journal = TransferJournal::load(&TransferJournal::default_path())?;
+            let addresses =
+                match parse_target_addresses(sub_matches, &journal, TransferKind::Config)? {
+                    Some(addresses) if addresses.is_empty() => {
+                        println!("No interrupted config transfers to resume.");
+                        return Ok(());
+                    }
+                    addresses => addresses,
+                };
+            let verify_delay = parse_verify_delay(sub_matches)?;
+            let chunk_size = parse_chunk_size(sub_matches)?;
+            let inventory = Inventory::load(&Inventory::default_path())?;
+            let watch = sub_matches.is_present("WATCH");
+            let params = sub_matches.value_of("PARAMS");
+            let mut session = None;
+            let reports = RefCell::new(vec![]);
+            let mut results = vec![];
+            let cancellation = CancellationToken::install_signal_handler()?;
 
-            let file = match File::open(config) {
-                Ok(file) => file,
-                Err(err) => {
-                    return Err(ConfiguratorError::IOError(err));
-                }
-            };
+            loop {
+                let watcher = ConfigWatcher::new(config_source_files(
+                    Path::new(config),
+                    params.map(Path::new),
+                    env,
+                ));
 
-            let mut source_code = String::new();
+                let loaded = match params {
+                    Some(params) => render_config_data(Path::new(config), Path::new(params), env)
+                        .map(|config_variants| (config_variants, vec![])),
+                    None => load_config_data(Path::new(config), env, require_checksum)
+                        .map(|config_data| (BTreeMap::new(), config_data)),
+                };
 
-            let mut reader = BufReader::new(file);
-            reader
-                .read_to_string(&mut source_code)
-                .map_err(|err| ConfiguratorError::IOError(err))?;
+                match loaded {
+                    Ok((config_variants, config_data)) => {
+                        if session.is_none() {
+                            let programmer = get_programmer(&mut protocol)?;
+                            let devices = get_devices(&mut protocol, &programmer)?;
 
-            let config = Parser::parse(&source_code).map_err(|err| {
-                eprintln!("Parsing failed with error:");
-                eprintln!("{}", err);
+                            session = Some((programmer, devices));
+                        }
 
-                ConfiguratorError::ParserError(err)
-            })?;
+                        let (programmer, devices) = session.as_ref().unwrap();
+                        let addresses = match &addresses {
+                            Some(addresses) => addresses.clone(),
+                            None if !config_variants.is_empty() => {
+                                config_variants.keys().copied().collect()
+                            }
+                            None => all_addresses(devices),
+                        };
 
-            let programmer = get_programmer(&mut protocol)?;
-            let devices = get_devices(&mut protocol, &programmer)?;
+                        results = run_for_each(&addresses, &cancellation, |address| {
+                            let config_data = config_variants.get(&address).unwrap_or(&config_data);
 
-            upgrade_config(&mut protocol, &programmer, &devices, &config, address)?;
+                            check_config_size(config_data, address, &inventory)?;
 
-            Ok(())
+                            if resume {
+                                journal.check_resume(address, TransferKind::Config, config_data)?;
+                            }
+
+                            upgrade_config(
+                                &mut protocol,
+                                programmer,
+                                devices,
+                                config_data,
+                                address,
+                                &mut journal,
+                                &TransferOptions {
+                                    chunk_size: inventory.chunk_size(address, chunk_size),
+                                    cancellation: &cancellation,
+                                    dry_run,
+                                    reports: &reports,
+                                },
+                            )?;
+
+                            if dry_run {
+                                return Ok(());
+                            }
+
+                            match verify_delay {
+                                Some(verify_delay) => verify_device(
+                                    &mut protocol,
+                                    programmer,
+                                    devices,
+                                    address,
+                                    verify_delay,
+                                    &cancellation,
+                                ),
+                                None => Ok(()),
+                            }
+                        });
+
+                        print_summary(&results);
+                    }
+                    Err(_) if watch => {
+                        eprintln!("Config was not uploaded, fix the errors above to retry.");
+                    }
+                    Err(err) => return Err(err),
+                }
+
+                if !watch || !watcher.wait_for_change(&cancellation) {
+                    break;
+                }
+
+                println!("Reloading config.");
+            }
+
+            if let Some(stats_file) = sub_matches.value_of("STATS_FILE") {
+                write_stats_file(Path::new(stats_file), &reports.borrow())?;
+            }
+
+            into_result(results)
         }
         ("set_device_address", sub_matches) => {
             let sub_matches = sub_matches.unwrap();
@@ -173,7 +553,14 @@ fn main() -> Result<(), ConfiguratorError> {
             let programmer = get_programmer(&mut protocol)?;
             let devices = get_devices(&mut protocol, &programmer)?;
 
-            set_device_address(&mut protocol, &programmer, &devices, new_address, address)?;
+            set_device_address(
+                &mut protocol,
+                &programmer,
+                &devices,
+                new_address,
+                address,
+                dry_run,
+            )?;
 
             Ok(())
         }
@@ -184,10 +571,254 @@ fn main() -> Result<(), ConfiguratorError> {
             let data = sub_matches.values_of("DATA").unwrap().collect();
 
             get_programmer(&mut protocol)?;
-            send_event(&mut protocol, event, data)?;
+            send_event(&mut protocol, event, data, dry_run)?;
 
             Ok(())
         }
         (_, _) => Ok(()),
     }
 }
+
+fn parse_target_addresses(
+    matches: &ArgMatches,
+    journal: &TransferJournal,
+    kind: TransferKind,
+) -> Result<Option<Vec<u16>>, ConfiguratorError> {
+    if matches.is_present("RESUME") {
+        return Ok(Some(journal.interrupted(kind)));
+    }
+
+    match matches.values_of("ADDRESS") {
+        Some(values) => Ok(Some(parse_addresses(&values.collect::<Vec<_>>())?)),
+        None => Ok(None),
+    }
+}
+
+fn parse_chunk_size(matches: &ArgMatches) -> Result<Option<ChunkSize>, ConfiguratorError> {
+    match matches.value_of("CHUNK_SIZE") {
+        Some(chunk_size_str) => match ChunkSize::parse(chunk_size_str) {
+            Some(chunk_size) => Ok(Some(chunk_size)),
+            None => {
+                eprintln!(
+                    "CHUNK_SIZE is not a number between 1 and {} or \"adaptive\".",
+                    MAX_CHUNK_SIZE
+                );
+                Err(ConfiguratorError::BadUsage)
+            }
+        },
+        None => Ok(None),
+    }
+}
+
+fn parse_verify_delay(matches: &ArgMatches) -> Result<Option<Duration>, ConfiguratorError> {
+    if !matches.is_present("VERIFY") {
+        return Ok(None);
+    }
+
+    match matches.value_of("VERIFY_DELAY") {
+        Some(delay_str) => match parse::<u64>(delay_str) {
+            Ok(delay) => Ok(Some(Duration::from_millis(delay))),
+            Err(_) => {
+                eprintln!("VERIFY_DELAY is not a number.");
+                Err(ConfiguratorError::BadUsage)
+            }
+        },
+        None => Ok(Some(Duration::from_millis(DEFAULT_VERIFY_DELAY_MS))),
+    }
+}
+
+fn parse_rollout_options(
+    matches: &ArgMatches,
+    addresses: &[u16],
+    dry_run: bool,
+) -> Result<RolloutOptions, ConfiguratorError> {
+    let canary = match matches.value_of("CANARY") {
+        Some(canary_str) => match parse::<u16>(canary_str) {
+            Ok(canary) => canary,
+            Err(_) => {
+                eprintln!("CANARY is not a number.");
+                return Err(ConfiguratorError::BadUsage);
+            }
+        },
+        None => match addresses.first() {
+            Some(canary) => *canary,
+            None => {
+                eprintln!("No devices to roll out to.");
+                return Err(ConfiguratorError::BadUsage);
+            }
+        },
+    };
+
+    let batch_size = match matches.value_of("BATCH_SIZE") {
+        Some(batch_size_str) => match parse::<usize>(batch_size_str) {
+            Ok(batch_size) if batch_size > 0 => batch_size,
+            _ => {
+                eprintln!("BATCH_SIZE is not a positive number.");
+                return Err(ConfiguratorError::BadUsage);
+            }
+        },
+        None => DEFAULT_ROLLOUT_BATCH_SIZE,
+    };
+
+    let max_failures = match matches.value_of("MAX_FAILURES") {
+        Some(max_failures_str) => match parse::<usize>(max_failures_str) {
+            Ok(max_failures) => max_failures,
+            Err(_) => {
+                eprintln!("MAX_FAILURES is not a number.");
+                return Err(ConfiguratorError::BadUsage);
+            }
+        },
+        None => 0,
+    };
+
+    let rediscovery_timeout = match matches.value_of("REDISCOVERY_TIMEOUT") {
+        Some(timeout_str) => match parse::<u64>(timeout_str) {
+            Ok(timeout) => timeout,
+            Err(_) => {
+                eprintln!("REDISCOVERY_TIMEOUT is not a number.");
+                return Err(ConfiguratorError::BadUsage);
+            }
+        },
+        None => DEFAULT_REDISCOVERY_TIMEOUT_MS,
+    };
+
+    Ok(RolloutOptions {
+        canary,
+        batch_size,
+        max_failures,
+        rediscovery_timeout: Duration::from_millis(rediscovery_timeout),
+        dry_run,
+    })
+}
+
+fn load_firmware(
+    matches: &ArgMatches,
+    ledger: &Ledger,
+    address: u16,
+    board: Option<&str>,
+) -> Result<Firmware, ConfiguratorError> {
+    if let Some(firmware) = matches.value_of("FIRMWARE") {
+        return Firmware::load(Path::new(firmware));
+    }
+
+    let selector = match VersionSelector::parse(matches.value_of("VERSION").unwrap()) {
+        Some(selector) => selector,
+        None => {
+            eprintln!("VERSION is not a valid version requirement.");
+            return Err(ConfiguratorError::BadUsage);
+        }
+    };
+
+    let board = match board.or_else(|| ledger.get(address).map(|entry| entry.board.as_str())) {
+        Some(board) => board,
+        None => {
+            eprintln!(
+                "Board type of device (address: {:#06x}) is unknown, specify it with --board.",
+                address
+            );
+            return Err(ConfiguratorError::UnknownBoard);
+        }
+    };
+
+    let repository = FirmwareRepository::new(FirmwareRepository::default_path());
+    let path = match repository.select(board, &selector) {
+        Ok(path) => path,
+        Err(err) => {
+            eprintln!("No matching firmware found for board {}.", board);
+            return Err(err);
+        }
+    };
+
+    Firmware::load(&path)
+}
+
+fn record_firmware(ledger: &mut Ledger, firmware: &Firmware, address: u16, board: Option<&str>) {
+    match (&firmware.metadata, board) {
+        (Some(metadata), _) => ledger.record(
+            address,
+            metadata.board.clone(),
+            Some(metadata.version.clone()),
+        ),
+        (None, Some(board)) => ledger.record(address, board.to_string(), None),
+        (None, None) => match ledger.get(address).map(|entry| entry.board.clone()) {
+            Some(board) => ledger.record(address, board, None),
+            None => ledger.forget(address),
+        },
+    }
+}
+
+fn manage_firmware(matches: &ArgMatches) -> Result<(), ConfiguratorError> {
+    let repository = FirmwareRepository::new(FirmwareRepository::default_path());
+
+    match matches.subcommand() {
+        ("list", sub_matches) => {
+            let board = sub_matches.and_then(|sub_matches| sub_matches.value_of("BOARD"));
+
+            for release in repository.list(board)? {
+                println!("{} {}", release.board, release.version);
+            }
+
+            Ok(())
+        }
+        ("import", sub_matches) => {
+            let firmware = sub_matches.unwrap().value_of("FIRMWARE").unwrap();
+
+            let release = repository.import(Path::new(firmware))?;
+
+            println!("Imported firmware {} {}", release.board, release.version);
+
+            Ok(())
+        }
+        ("prune", sub_matches) => {
+            let sub_matches = sub_matches.unwrap();
+
+            let board = sub_matches.value_of("BOARD");
+            let keep = match sub_matches.value_of("KEEP") {
+                Some(keep) => match parse::<usize>(keep) {
+                    Ok(keep) => keep,
+                    Err(_) => {
+                        eprintln!("KEEP is not a number.");
+                        return Err(ConfiguratorError::BadUsage);
+                    }
+                },
+                None => DEFAULT_FIRMWARE_KEEP_COUNT,
+            };
+
+            for release in repository.prune(board, keep)? {
+                println!("Removed firmware {} {}", release.board, release.version);
+            }
+
+            Ok(())
+        }
+        (_, _) => Ok(()),
+    }
+}
+
+fn manage_config(
+    matches: &ArgMatches,
+    env: Option<&str>,
+    require_checksum: bool,
+) -> Result<(), ConfiguratorError> {
+    match matches.subcommand() {
+        ("diff", Some(sub_matches)) => config_diff(
+            Path::new(sub_matches.value_of("OLD").unwrap()),
+            Path::new(sub_matches.value_of("NEW").unwrap()),
+            env,
+            require_checksum,
+        ),
+        ("graph", Some(sub_matches)) => {
+            let format = match sub_matches.value_of("FORMAT") {
+                Some("mermaid") => GraphFormat::Mermaid,
+                _ => GraphFormat::Dot,
+            };
+
+            config_graph(
+                Path::new(sub_matches.value_of("CONFIG").unwrap()),
+                format,
+                env,
+                require_checksum,
+            )
+        }
+        (_, _) => Ok(()),
+    }
+}
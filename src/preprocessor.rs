@@ -0,0 +1,824 @@
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use crate::diagnostics::{Diagnostic, Severity};
+use crate::ross_configurator::*;
+
+pub const INCLUDE_DIRECTIVE: &str = "include";
+pub const CONST_DIRECTIVE: &str = "const";
+pub const CONST_DIRECTIVE_ALIAS: &str = "@const";
+const DEFAULT_CONSTANT_TYPE: &str = "u16";
+const LET_KEYWORD: &str = "let";
+const CONST_KEYWORD: &str = "const";
+
+pub const DSL_KEYWORDS: &[&str] = &[
+    "let",
+    "const",
+    "send",
+    "from",
+    "to",
+    "if",
+    "do",
+    "match",
+    "event",
+    "producer",
+    "tick",
+    "fire",
+    "set",
+    "on",
+    "true",
+    "false",
+    "not",
+    "or",
+    "and",
+    "peripheral",
+    "pub",
+    "bcm",
+    "single",
+    "rgb",
+    "rgbw",
+    "relay",
+    "double_exclusive",
+    "none",
+    "first",
+    "second",
+    "u8",
+    "u16",
+    "u32",
+    "bool",
+];
+
+struct Substitution {
+    column: usize,
+    length: usize,
+    original_column: usize,
+    original_length: usize,
+}
+
+pub struct SourceLine {
+    pub file: String,
+    pub line: usize,
+    pub text: String,
+    substitutions: Vec<Substitution>,
+}
+
+#[derive(Default)]
+pub struct Definitions {
+    pub aliases: BTreeMap<String, String>,
+    pub overlay: BTreeMap<String, String>,
+    pub parameters: BTreeMap<String, String>,
+}
+
+pub struct PreprocessedSource {
+    pub files: Vec<PathBuf>,
+    pub text: String,
+    pub lines: Vec<SourceLine>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl SourceLine {
+    fn original_column(&self, column: usize) -> usize {
+        let mut original_end = 0;
+        let mut end = 0;
+
+        for substitution in self.substitutions.iter() {
+            if column < substitution.column {
+                break;
+            }
+
+            if column < substitution.column + substitution.length {
+                return substitution.original_column;
+            }
+
+            original_end = substitution.original_column + substitution.original_length;
+            end = substitution.column + substitution.length;
+        }
+
+        original_end + column - end
+    }
+}
+
+impl PreprocessedSource {
+    pub fn diagnostic(
+        &self,
+        severity: Severity,
+        message: String,
+        (line, column): (usize, usize),
+        length: usize,
+    ) -> Diagnostic {
+        let source_line = match self.lines.get(line - 1).or_else(|| self.lines.last()) {
+            Some(source_line) => source_line,
+            None => return Diagnostic::new(severity, message, "", "", (1, 1), length),
+        };
+
+        let original_column = source_line.original_column(column - 1);
+        let original_end = source_line.original_column(column - 1 + length);
+
+        Diagnostic::new(
+            severity,
+            message,
+            &source_line.file,
+            &source_line.text,
+            (source_line.line, original_column + 1),
+            original_end.saturating_sub(original_column),
+        )
+    }
+
+    pub fn substituted_word(&self, file: &str, line: usize, column: usize) -> Option<String> {
+        let index = self
+            .lines
+            .iter()
+            .position(|source_line| source_line.file == file && source_line.line == line)?;
+        let substitution = self.lines[index]
+            .substitutions
+            .iter()
+            .find(|substitution| {
+                (substitution.original_column
+                    ..substitution.original_column + substitution.original_length)
+                    .contains(&column)
+            })?;
+
+        Some(
+            self.text
+                .lines()
+                .nth(index)?
+                .chars()
+                .skip(substitution.column)
+                .take(substitution.length)
+                .collect(),
+        )
+    }
+
+    fn push_line(
+        &mut self,
+        file: &str,
+        line: usize,
+        original: &str,
+        text: String,
+        substitutions: Vec<Substitution>,
+    ) {
+        self.text += &text;
+        self.text += "\n";
+        self.lines.push(SourceLine {
+            file: file.to_string(),
+            line,
+            text: original.to_string(),
+            substitutions,
+        });
+    }
+}
+
+struct Preprocessor<'a> {
+    definitions: &'a Definitions,
+    constants: BTreeMap<String, String>,
+    include_stack: Vec<PathBuf>,
+    source: PreprocessedSource,
+}
+
+impl<'a> Preprocessor<'a> {
+    fn process_file(&mut self, path: &Path) -> Result<(), String> {
+        let text = fs::read_to_string(path).map_err(|err| err.to_string())?;
+
+        self.process_text(path, &text)
+    }
+
+    fn process_text(&mut self, path: &Path, text: &str) -> Result<(), String> {
+        let canonical_path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+
+        if self.include_stack.contains(&canonical_path) {
+            return Err(format!("{} includes itself", path.display()));
+        }
+
+        let file = path.to_string_lossy().to_string();
+
+        self.source.files.push(path.to_path_buf());
+        self.include_stack.push(canonical_path);
+
+        for (i, line) in text.lines().enumerate() {
+            let code = line.split("//").next().unwrap_or_default();
+            let trimmed = code.trim();
+            let indent = code.len() - code.trim_start().len();
+
+            if let Some(argument) = directive_argument(trimmed, INCLUDE_DIRECTIVE) {
+                self.source
+                    .push_line(&file, i + 1, line, String::new(), vec![]);
+
+                let argument = argument.trim_end_matches(';').trim_end();
+
+                if argument.len() < 2 || !argument.starts_with('"') || !argument.ends_with('"') {
+                    self.error(
+                        &file,
+                        line,
+                        (i + 1, indent),
+                        trimmed,
+                        "expected a quoted path after `include`".to_string(),
+                    );
+                    continue;
+                }
+
+                let include_path = path
+                    .parent()
+                    .unwrap_or_else(|| Path::new(""))
+                    .join(&argument[1..argument.len() - 1]);
+
+                if let Err(err) = self.process_file(&include_path) {
+                    self.error(
+                        &file,
+                        line,
+                        (i + 1, indent),
+                        trimmed,
+                        format!("could not include {}: {}", include_path.display(), err),
+                    );
+                }
+            } else if let Some((directive, argument)) = const_directive(trimmed) {
+                self.source
+                    .push_line(&file, i + 1, line, String::new(), vec![]);
+
+                let (name, value) = match argument.split_once('=') {
+                    Some((name, value))
+                        if is_identifier(name.trim()) && !value.trim().is_empty() =>
+                    {
+                        (name.trim(), value.trim())
+                    }
+                    _ => {
+                        self.error(
+                            &file,
+                            line,
+                            (i + 1, indent),
+                            trimmed,
+                            format!("expected `{} NAME = value`", directive),
+                        );
+                        continue;
+                    }
+                };
+
+                if DSL_KEYWORDS.contains(&name) {
+                    self.error(
+                        &file,
+                        line,
+                        (i + 1, indent),
+                        trimmed,
+                        format!(
+                            "constant `{}` would replace the DSL keyword `{}`",
+                            name, name
+                        ),
+                    );
+                    continue;
+                }
+
+                if self.constants.contains_key(name) {
+                    self.error(
+                        &file,
+                        line,
+                        (i + 1, indent),
+                        trimmed,
+                        format!("constant `{}` is already defined", name),
+                    );
+                    continue;
+                }
+
+                let value = match self.definitions.overlay.get(name) {
+                    Some(value) => value.clone(),
+                    None => match typed_value(&self.substitute(&file, (i + 1, line), value).0) {
+                        Ok(value) => value,
+                        Err(message) => {
+                            self.error(&file, line, (i + 1, indent), trimmed, message);
+                            continue;
+                        }
+                    },
+                };
+
+                self.constants.insert(name.to_string(), value);
+            } else {
+                if let Some(name) = declared_name(trimmed) {
+                    if let Some(source) = self.definition_source(name) {
+                        self.error(
+                            &file,
+                            line,
+                            (i + 1, indent),
+                            trimmed,
+                            format!(
+                                "`{}` is declared here but is also defined as {}, rename one of them",
+                                name, source
+                            ),
+                        );
+                    }
+                }
+
+                let (text, substitutions) = self.substitute(&file, (i + 1, line), code);
+
+                self.source
+                    .push_line(&file, i + 1, line, text, substitutions);
+            }
+        }
+
+        self.include_stack.pop();
+
+        Ok(())
+    }
+
+    fn substitute(
+        &mut self,
+        file: &str,
+        (line_number, line): (usize, &str),
+        code: &str,
+    ) -> (String, Vec<Substitution>) {
+        let mut text = String::new();
+        let mut substitutions = vec![];
+        let mut chars = code.chars().enumerate().peekable();
+        let mut column = 0;
+        let mut in_string = false;
+        let mut previous = ' ';
+
+        while let Some((original_column, c)) = chars.next() {
+            if c == '$' && chars.peek().map(|(_, next)| *next) == Some('{') {
+                chars.next();
+
+                let mut name = String::new();
+
+                for (_, next) in chars.by_ref() {
+                    if next == '}' {
+                        break;
+                    }
+
+                    name.push(next);
+                }
+
+                let placeholder_length = name.chars().count() + 3;
+
+                match self.definitions.parameters.get(name.trim()) {
+                    Some(value) => {
+                        let length = value.chars().count();
+
+                        substitutions.push(Substitution {
+                            column,
+                            length,
+                            original_column,
+                            original_length: placeholder_length,
+                        });
+
+                        text += value;
+                        column += length;
+                    }
+                    None => {
+                        self.source.diagnostics.push(Diagnostic::new(
+                            Severity::Error,
+                            format!("unknown template parameter `{}`", name.trim()),
+                            file,
+                            line,
+                            (line_number, original_column + 1),
+                            placeholder_length,
+                        ));
+                    }
+                }
+
+                previous = '}';
+                continue;
+            }
+
+            if c == '"' {
+                in_string = !in_string;
+            }
+
+            if in_string
+                || !(c.is_alphabetic() || c == '_')
+                || is_word_char(previous)
+                || previous == '#'
+            {
+                text.push(c);
+                column += 1;
+                previous = c;
+                continue;
+            }
+
+            let mut word = c.to_string();
+
+            while let Some((_, next)) = chars.peek().copied() {
+                if !is_word_char(next) {
+                    break;
+                }
+
+                word.push(next);
+                chars.next();
+            }
+
+            let replacement = self
+                .constants
+                .get(&word)
+                .or_else(|| self.definitions.overlay.get(&word))
+                .or_else(|| self.definitions.aliases.get(&word));
+
+            match replacement {
+                Some(replacement) => {
+                    let length = replacement.chars().count();
+
+                    substitutions.push(Substitution {
+                        column,
+                        length,
+                        original_column,
+                        original_length: word.chars().count(),
+                    });
+
+                    text += replacement;
+                    column += length;
+                }
+                None => {
+                    column += word.chars().count();
+                    text += &word;
+                }
+            }
+
+            previous = word.chars().last().unwrap_or(c);
+        }
+
+        (text, substitutions)
+    }
+
+    fn definition_source(&self, name: &str) -> Option<&'static str> {
+        if self.constants.contains_key(name) {
+            Some("a constant")
+        } else if self.definitions.overlay.contains_key(name) {
+            Some("an overlay value")
+        } else if self.definitions.aliases.contains_key(name) {
+            Some("an alias")
+        } else {
+            None
+        }
+    }
+
+    fn error(
+        &mut self,
+        file: &str,
+        source_line: &str,
+        (line, column): (usize, usize),
+        directive: &str,
+        message: String,
+    ) {
+        self.source.diagnostics.push(Diagnostic::new(
+            Severity::Error,
+            message,
+            file,
+            source_line,
+            (line, source_line[..column].chars().count() + 1),
+            directive.chars().count(),
+        ));
+    }
+}
+
+pub fn aliases_path() -> PathBuf {
+    data_directory().join(ALIASES_FILE_NAME)
+}
+
+pub fn load_aliases(path: &Path) -> Result<BTreeMap<String, String>, ConfiguratorError> {
+    match fs::read_to_string(path) {
+        Ok(string) => parse_definitions(path, &string),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(BTreeMap::new()),
+        Err(err) => Err(ConfiguratorError::IOError(err)),
+    }
+}
+
+pub fn overlay_path(config_path: &Path, env: &str) -> PathBuf {
+    let stem = config_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    config_path.with_file_name(format!("{}.{}.{}", stem, env, OVERLAY_EXTENSION))
+}
+
+pub fn load_overlay(
+    config_path: &Path,
+    env: &str,
+) -> Result<BTreeMap<String, String>, ConfiguratorError> {
+    let path = overlay_path(config_path, env);
+
+    let string = match fs::read_to_string(&path) {
+        Ok(string) => string,
+        Err(err) if err.kind() == ErrorKind::NotFound => {
+            eprintln!("No \"{}\" overlay found at {}.", env, path.display());
+            return Err(ConfiguratorError::BadUsage);
+        }
+        Err(err) => return Err(ConfiguratorError::IOError(err)),
+    };
+
+    eprintln!("Applying \"{}\" overlay from {}.", env, path.display());
+
+    parse_definitions(&path, &string)
+}
+
+fn parse_definitions(
+    path: &Path,
+    string: &str,
+) -> Result<BTreeMap<String, String>, ConfiguratorError> {
+    let table: BTreeMap<String, toml::Value> =
+        toml::from_str(string).map_err(ConfiguratorError::TomlError)?;
+
+    let mut definitions = BTreeMap::new();
+
+    for (name, value) in table.into_iter() {
+        if DSL_KEYWORDS.contains(&name.as_str()) {
+            eprintln!(
+                "\"{}\" in {} would replace the DSL keyword \"{}\".",
+                name,
+                path.display(),
+                name
+            );
+            return Err(ConfiguratorError::BadUsage);
+        }
+
+        let value = match value {
+            toml::Value::Integer(value) => match u16::try_from(value) {
+                Ok(value) => format!("{:#06x}~{}", value, DEFAULT_CONSTANT_TYPE),
+                Err(_) => {
+                    eprintln!(
+                        "\"{}\" in {} does not fit in {}, write it as a typed string such as \"{}~u32\".",
+                        name,
+                        path.display(),
+                        DEFAULT_CONSTANT_TYPE,
+                        value
+                    );
+                    return Err(ConfiguratorError::BadUsage);
+                }
+            },
+            toml::Value::String(value) => match typed_value(&value) {
+                Ok(value) => value,
+                Err(message) => {
+                    eprintln!("\"{}\" in {}: {}.", name, path.display(), message);
+                    return Err(ConfiguratorError::BadUsage);
+                }
+            },
+            _ => {
+                eprintln!(
+                    "\"{}\" in {} is neither an integer nor a string.",
+                    name,
+                    path.display()
+                );
+                return Err(ConfiguratorError::BadUsage);
+            }
+        };
+
+        definitions.insert(name, value);
+    }
+
+    Ok(definitions)
+}
+
+pub fn preprocess(
+    path: &Path,
+    definitions: &Definitions,
+) -> Result<PreprocessedSource, ConfiguratorError> {
+    let text = fs::read_to_string(path).map_err(ConfiguratorError::IOError)?;
+
+    preprocess_text(path, &text, definitions)
+}
+
+pub fn preprocess_text(
+    path: &Path,
+    text: &str,
+    definitions: &Definitions,
+) -> Result<PreprocessedSource, ConfiguratorError> {
+    let mut preprocessor = Preprocessor {
+        definitions,
+        constants: BTreeMap::new(),
+        include_stack: vec![],
+        source: PreprocessedSource {
+            files: vec![],
+            text: String::new(),
+            lines: vec![],
+            diagnostics: vec![],
+        },
+    };
+
+    preprocessor
+        .process_text(path, text)
+        .map_err(|err| ConfiguratorError::IOError(std::io::Error::other(err)))?;
+
+    Ok(preprocessor.source)
+}
+
+pub fn directive_argument<'t>(line: &'t str, directive: &str) -> Option<&'t str> {
+    let argument = line.strip_prefix(directive)?;
+
+    if !argument.starts_with(|c: char| c.is_whitespace() || c == '"') {
+        return None;
+    }
+
+    Some(argument.trim())
+}
+
+// `const NAME = value` without a trailing `;` is a preprocessor constant, with one it is a DSL
+// constant statement. `@const` is always a preprocessor constant.
+pub fn const_directive(line: &str) -> Option<(&'static str, &str)> {
+    if let Some(argument) = directive_argument(line, CONST_DIRECTIVE_ALIAS) {
+        return Some((CONST_DIRECTIVE_ALIAS, argument));
+    }
+
+    directive_argument(line, CONST_DIRECTIVE)
+        .filter(|argument| !argument.ends_with(';'))
+        .map(|argument| (CONST_DIRECTIVE, argument))
+}
+
+fn typed_value(value: &str) -> Result<String, String> {
+    match parse_int::parse::<i128>(value) {
+        Ok(number) if u16::try_from(number).is_ok() => {
+            Ok(format!("{}~{}", value, DEFAULT_CONSTANT_TYPE))
+        }
+        Ok(_) => Err(format!(
+            "`{}` does not fit in {}, add an explicit type such as `{}~u32`",
+            value, DEFAULT_CONSTANT_TYPE, value
+        )),
+        Err(_) => Ok(value.to_string()),
+    }
+}
+
+fn declared_name(line: &str) -> Option<&str> {
+    let argument = directive_argument(line, LET_KEYWORD)
+        .or_else(|| directive_argument(line, CONST_KEYWORD))?;
+    let name = argument.split('=').next()?.trim();
+
+    if is_identifier(name) {
+        Some(name)
+    } else {
+        None
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_alphabetic() || c == '_') && name.chars().all(is_word_char)
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+    use std::process;
+
+    fn preprocess_files(
+        name: &str,
+        files: &[(&str, &str)],
+        definitions: &Definitions,
+    ) -> PreprocessedSource {
+        let directory = env::temp_dir().join(format!(
+            "ross_configurator_preprocessor_{}_{}",
+            name,
+            process::id()
+        ));
+        fs::create_dir_all(&directory).unwrap();
+
+        for (file, text) in files.iter() {
+            fs::write(directory.join(file), text).unwrap();
+        }
+
+        let source = preprocess(&directory.join(files[0].0), definitions).unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+
+        source
+    }
+
+    fn messages(source: &PreprocessedSource) -> Vec<&str> {
+        source
+            .diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.message.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn constants_are_typed_and_substituted() {
+        let source = preprocess_files(
+            "constants",
+            &[(
+                "main.ross",
+                "const LIGHT = 0x0010\n@const WIDE = 70000~u32\nsend LIGHT to WIDE;\n",
+            )],
+            &Definitions::default(),
+        );
+
+        assert!(source.diagnostics.is_empty());
+        assert_eq!(source.text, "\n\nsend 0x0010~u16 to 70000~u32;\n");
+    }
+
+    #[test]
+    fn out_of_range_constants_are_rejected() {
+        let source = preprocess_files(
+            "range",
+            &[("main.ross", "const WIDE = 70000\nconst NEGATIVE = -1\n")],
+            &Definitions::default(),
+        );
+
+        assert_eq!(source.diagnostics.len(), 2);
+        assert!(messages(&source)[0].contains("does not fit in u16"));
+    }
+
+    #[test]
+    fn malformed_constants_name_the_directive_used() {
+        let source = preprocess_files(
+            "malformed",
+            &[(
+                "main.ross",
+                "const = 1
+@const LIGHT
+",
+            )],
+            &Definitions::default(),
+        );
+
+        assert_eq!(
+            messages(&source),
+            vec![
+                "expected `const NAME = value`",
+                "expected `@const NAME = value`"
+            ]
+        );
+    }
+
+    #[test]
+    fn dsl_constant_statements_are_not_directives() {
+        let source = preprocess_files(
+            "dsl_const",
+            &[("main.ross", "const LIGHT = 0x0010~u16;\n")],
+            &Definitions::default(),
+        );
+
+        assert!(source.diagnostics.is_empty());
+        assert_eq!(source.text, "const LIGHT = 0x0010~u16;\n");
+    }
+
+    #[test]
+    fn includes_are_expanded_in_place() {
+        let source = preprocess_files(
+            "include",
+            &[
+                ("main.ross", "include \"common.ross\";\nsend LIGHT;\n"),
+                ("common.ross", "const LIGHT = 1\n"),
+            ],
+            &Definitions::default(),
+        );
+
+        assert!(source.diagnostics.is_empty());
+        assert_eq!(source.files.len(), 2);
+        assert_eq!(source.text, "\n\nsend 1~u16;\n");
+    }
+
+    #[test]
+    fn keyword_constants_are_rejected() {
+        let source = preprocess_files(
+            "keyword",
+            &[("main.ross", "@const send = 1\n")],
+            &Definitions::default(),
+        );
+
+        assert_eq!(
+            messages(&source),
+            vec!["constant `send` would replace the DSL keyword `send`"]
+        );
+    }
+
+    #[test]
+    fn declarations_shadowed_by_aliases_are_reported() {
+        let mut definitions = Definitions::default();
+        definitions
+            .aliases
+            .insert("light".to_string(), "0x0010~u16".to_string());
+
+        let source = preprocess_files(
+            "shadow",
+            &[("main.ross", "let light = 0~u8;\n")],
+            &definitions,
+        );
+
+        assert_eq!(source.diagnostics.len(), 1);
+        assert!(messages(&source)[0].contains("also defined as an alias"));
+    }
+
+    #[test]
+    fn substitutions_map_back_to_original_columns() {
+        let mut definitions = Definitions::default();
+        definitions
+            .aliases
+            .insert("L".to_string(), "0x0010~u16".to_string());
+
+        let source = preprocess_files("columns", &[("main.ross", "send L to 5;\n")], &definitions);
+        let diagnostic = source.diagnostic(Severity::Error, "test".to_string(), (1, 17), 1);
+
+        assert_eq!(source.text, "send 0x0010~u16 to 5;\n");
+        assert_eq!(diagnostic.column, 8);
+    }
+
+    #[test]
+    fn typed_values_keep_explicit_types() {
+        assert_eq!(typed_value("5"), Ok("5~u16".to_string()));
+        assert_eq!(typed_value("0xffff"), Ok("0xffff~u16".to_string()));
+        assert_eq!(typed_value("5~u8"), Ok("5~u8".to_string()));
+        assert_eq!(typed_value("true"), Ok("true".to_string()));
+        assert!(typed_value("0x10000").is_err());
+    }
+}
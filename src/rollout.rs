@@ -0,0 +1,173 @@
+use std::time::Duration;
+
+use ross_protocol::event::programmer::ProgrammerHelloEvent;
+use ross_protocol::interface::serial::Serial;
+use ross_protocol::protocol::Protocol;
+
+use crate::batch::{run_device, DeviceResult};
+use crate::cancellation::CancellationToken;
+use crate::get_devices::wait_for_devices;
+use crate::ross_configurator::*;
+
+pub struct RolloutOptions {
+    pub canary: u16,
+    pub batch_size: usize,
+    pub max_failures: usize,
+    pub rediscovery_timeout: Duration,
+    pub dry_run: bool,
+}
+
+pub fn rollout<F>(
+    protocol: &mut Protocol<Serial>,
+    programmer: &ProgrammerHelloEvent,
+    addresses: &[u16],
+    options: &RolloutOptions,
+    cancellation: &CancellationToken,
+    mut upgrade: F,
+) -> Result<Vec<DeviceResult>, ConfiguratorError>
+where
+    F: FnMut(&mut Protocol<Serial>, u16) -> Result<(), ConfiguratorError>,
+{
+    if !addresses.contains(&options.canary) {
+        eprintln!("Canary device is not one of the targeted devices.");
+        return Err(ConfiguratorError::BadUsage);
+    }
+
+    let mut stages = vec![vec![options.canary]];
+
+    let remaining: Vec<u16> = addresses
+        .iter()
+        .copied()
+        .filter(|address| *address != options.canary)
+        .collect();
+
+    for batch in remaining.chunks(options.batch_size.max(1)) {
+        stages.push(batch.to_vec());
+    }
+
+    let mut results: Vec<DeviceResult> = vec![];
+    let mut halted = false;
+
+    for (i, stage) in stages.iter().enumerate() {
+        if i == 0 {
+            println!(
+                "Upgrading canary device (address: {:#06x}).",
+                options.canary
+            );
+        } else {
+            println!("Upgrading batch {} of {}.", i, stages.len() - 1);
+        }
+
+        let mut rebooting = vec![];
+
+        for (j, address) in stage.iter().enumerate() {
+            let result = run_device((j + 1, stage.len()), *address, |address| {
+                upgrade(protocol, address)
+            });
+
+            if result.result.is_ok() && !options.dry_run {
+                rebooting.push(results.len());
+            }
+
+            results.push(result);
+
+            if should_halt(&results, i, options) || cancellation.is_cancelled() {
+                halted = true;
+                break;
+            }
+        }
+
+        if !rebooting.is_empty() {
+            println!("Waiting for upgraded devices to reappear.");
+
+            let addresses: Vec<u16> = rebooting
+                .iter()
+                .map(|index| results[*index].address)
+                .collect();
+
+            match wait_for_devices(
+                protocol,
+                programmer,
+                &addresses,
+                options.rediscovery_timeout,
+                cancellation,
+            ) {
+                Ok(rediscovered) => {
+                    for index in rebooting.iter() {
+                        let result = &mut results[*index];
+
+                        if !rediscovered.contains(&result.address) {
+                            eprintln!(
+                                "Device (address: {:#06x}) did not reappear after the upgrade.",
+                                result.address
+                            );
+                            result.result = Err(ConfiguratorError::DeviceNotRediscovered);
+                        }
+                    }
+                }
+                Err(err) => {
+                    eprintln!(
+                        "Could not confirm that upgraded devices reappeared: {:?}",
+                        err
+                    );
+
+                    for index in rebooting.iter() {
+                        results[*index].result = Err(ConfiguratorError::DeviceNotRediscovered);
+                    }
+
+                    halted = true;
+                }
+            }
+        }
+
+        if halted || should_halt(&results, i, options) || cancellation.is_cancelled() {
+            let failure_count = failure_count(&results);
+
+            if cancellation.is_cancelled() {
+                eprintln!(
+                    "Halting rollout after cancellation, remaining devices were not upgraded."
+                );
+            } else {
+                eprintln!(
+                    "Halting rollout after {} failure(s), remaining devices were not upgraded.",
+                    failure_count
+                );
+            }
+
+            let attempted: Vec<u16> = results.iter().map(|result| result.address).collect();
+
+            for address in stages[i..]
+                .iter()
+                .flatten()
+                .filter(|address| !attempted.contains(address))
+            {
+                results.push(DeviceResult {
+                    address: *address,
+                    duration: Duration::from_secs(0),
+                    result: Err(ConfiguratorError::RolloutHalted),
+                });
+            }
+
+            break;
+        }
+    }
+
+    Ok(results)
+}
+
+fn should_halt(results: &[DeviceResult], stage: usize, options: &RolloutOptions) -> bool {
+    let failure_count = failure_count(results);
+
+    if stage == 0 {
+        failure_count > 0
+    } else {
+        failure_count > options.max_failures
+    }
+}
+
+fn failure_count(results: &[DeviceResult]) -> usize {
+    results
+        .iter()
+        .filter(|result| result.result.is_err())
+        .count()
+}
@@ -1,19 +1,84 @@
 use ross_config::serializer::ConfigSerializerError;
 use ross_dsl::error::ParserError;
 use ross_protocol::protocol::ProtocolError;
+use std::env;
 use std::io::Error as IOError;
+use std::path::PathBuf;
 
 pub const PACKET_TIMEOUT_MS: u64 = 100;
 pub const DEFAULT_BAUDRATE: u64 = 115_200;
 pub const DATA_PACKET_SIZE: usize = 128;
+pub const MAX_CHUNK_SIZE: usize = 1024;
+pub const ADAPTIVE_INITIAL_CHUNK_SIZE: usize = 512;
+pub const ADAPTIVE_MIN_CHUNK_SIZE: usize = 16;
+pub const ADAPTIVE_GROW_SUCCESSES: usize = 16;
+pub const TRANSFER_RESTART_LIMIT: usize = 3;
+pub const JOURNAL_SAVE_INTERVAL_BYTES: usize = 4096;
+pub const REDISCOVERY_INTERVAL_MS: u64 = 500;
+pub const WATCH_INTERVAL_MS: u64 = 500;
+pub const CANCELLATION_POLL_INTERVAL_MS: u64 = 50;
+pub const DEFAULT_REDISCOVERY_TIMEOUT_MS: u64 = 10_000;
+pub const DEFAULT_VERIFY_DELAY_MS: u64 = 2_000;
+pub const DEFAULT_ROLLOUT_BATCH_SIZE: usize = 5;
+pub const DATA_DIRECTORY_ENV: &str = "ROSS_CONFIGURATOR_HOME";
+pub const DATA_DIRECTORY_NAME: &str = ".ross-configurator";
+pub const LEDGER_FILE_NAME: &str = "ledger.json";
+pub const TRANSFER_JOURNAL_FILE_NAME: &str = "transfers.json";
+pub const INVENTORY_FILE_NAME: &str = "inventory.toml";
+pub const ALIASES_FILE_NAME: &str = "aliases.toml";
+pub const OVERLAY_EXTENSION: &str = "toml";
+pub const CONFIG_CHECKSUM_EXTENSION: &str = "sha256";
+pub const CONFIG_BLOB_EXTENSION: &str = "bin";
+pub const YAML_PARAMETER_EXTENSIONS: [&str; 2] = ["yaml", "yml"];
+pub const FIRMWARE_REPOSITORY_DIRECTORY_NAME: &str = "firmware";
+pub const DEFAULT_FIRMWARE_KEEP_COUNT: usize = 3;
+pub const MAX_DIAGNOSTICS: usize = 10;
+pub const CONFIG_SIZE_BREAKDOWN_LIMIT: usize = 5;
 
 #[derive(Debug)]
 pub enum ConfiguratorError {
     BadUsage,
     DeviceNotFound,
+    FirmwareNotFound,
+    UnknownBoard,
+    UnknownProfile,
+    IncompatibleFirmware,
+    FirmwareDowngrade,
+    DeviceNotRediscovered,
+    DeviceAddressChanged,
+    RolloutHalted,
+    TransferMismatch,
+    ChecksumMismatch,
+    ChecksumMissing,
+    Cancelled { address: u16, offset: usize },
+    WaitCancelled,
+    SkippedAfterCancellation,
+    InvalidFirmwareMetadata,
+    LintFailed,
+    ConfigTooLarge,
+    PreprocessorError,
+    NotFormatted,
+    FormatterError,
+    SimulationFailed,
     IOError(IOError),
     FailedToOpenDevice(serialport::Error),
     ProtocolError(ProtocolError),
     ParserError(ParserError<String>),
     ConfigSerializerError(ConfigSerializerError),
+    JsonError(serde_json::Error),
+    TomlError(toml::de::Error),
+    TomlSerializeError(toml::ser::Error),
+    YamlError(serde_yaml::Error),
+    SignalHandlerError(ctrlc::Error),
+}
+
+pub fn data_directory() -> PathBuf {
+    if let Some(directory) = env::var_os(DATA_DIRECTORY_ENV) {
+        return PathBuf::from(directory);
+    }
+
+    match env::var_os("HOME") {
+        Some(home) => PathBuf::from(home).join(DATA_DIRECTORY_NAME),
+        None => PathBuf::from(DATA_DIRECTORY_NAME),
+    }
 }
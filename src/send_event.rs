@@ -1,7 +1,6 @@
 use parse_int::parse;
 
 use ross_protocol::convert_packet::ConvertPacket;
-use ross_protocol::event::bcm::*;
 use ross_protocol::event::bootloader::*;
 use ross_protocol::event::button::*;
 use ross_protocol::event::configurator::*;
@@ -9,6 +8,7 @@ use ross_protocol::event::general::*;
 use ross_protocol::event::internal::*;
 use ross_protocol::event::programmer::*;
 use ross_protocol::interface::serial::Serial;
+use ross_protocol::packet::Packet;
 use ross_protocol::protocol::Protocol;
 
 use crate::event_type::EventType;
@@ -19,7 +19,50 @@ pub fn send_event(
     protocol: &mut Protocol<Serial>,
     event: EventType,
     data: Vec<&str>,
+    dry_run: bool,
 ) -> Result<(), ConfiguratorError> {
+    let packet = event_packet(event, data)?;
+
+    if dry_run {
+        println!("Would send packet ({:?}).", packet);
+        return Ok(());
+    }
+
+    protocol
+        .add_packet_handler(
+            Box::new(|packet, _can| {
+                println!("Received packet ({:?})", packet);
+            }),
+            false,
+        )
+        .unwrap();
+
+    match protocol.send_packet(&packet) {
+        Ok(()) => {
+            println!("Sent packet ({:?}).", packet);
+            Ok(())
+        }
+        Err(err) => Err(ConfiguratorError::ProtocolError(err)),
+    }
+}
+
+pub fn event_packet(event: EventType, data: Vec<&str>) -> Result<Packet, ConfiguratorError> {
+    let argument_count = match event {
+        ConfiguratorHello => 0,
+        ProgrammerHello | SystemTick => 1,
+        Ack | BootloaderHello => 2,
+        ProgrammerStartFirmwareUpgrade
+        | ProgrammerStartConfigUpgrade
+        | ButtonPressed
+        | ButtonReleased
+        | Data => 3,
+    };
+
+    if data.len() < argument_count {
+        eprintln!("{} takes at least {} arguments.", event, argument_count);
+        return Err(ConfiguratorError::BadUsage);
+    }
+
     let packet = match event {
         Ack => {
             let receiver_address = parse_u16(data[0], "receiver_address")?;
@@ -87,8 +130,8 @@ pub fn send_event(
         }
         ProgrammerStartConfigUpgrade => {
             let receiver_address = parse_u16(data[0], "receiver_address")?;
-            let programmer_address = parse_u16(data[0], "programmer_address")?;
-            let config_size = parse_u32(data[1], "config_size")?;
+            let programmer_address = parse_u16(data[1], "programmer_address")?;
+            let config_size = parse_u32(data[2], "config_size")?;
 
             ProgrammerStartConfigUpgradeEvent {
                 receiver_address,
@@ -129,22 +172,7 @@ pub fn send_event(
         }
     };
 
-    protocol
-        .add_packet_handler(
-            Box::new(|packet, _can| {
-                println!("Received packet ({:?})", packet);
-            }),
-            false,
-        )
-        .unwrap();
-
-    match protocol.send_packet(&packet) {
-        Ok(()) => {
-            println!("Sent packet ({:?}).", packet);
-            Ok(())
-        }
-        Err(err) => Err(ConfiguratorError::ProtocolError(err)),
-    }
+    Ok(packet)
 }
 
 fn parse_u8(string: &str, name: &str) -> Result<u8, ConfiguratorError> {
@@ -176,3 +204,24 @@ fn parse_u32(string: &str, name: &str) -> Result<u32, ConfiguratorError> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn start_config_upgrade_takes_receiver_programmer_and_size() {
+        let packet =
+            event_packet(ProgrammerStartConfigUpgrade, vec!["0x0010", "0x0001", "76"]).unwrap();
+        let event = ProgrammerStartConfigUpgradeEvent::try_from_packet(&packet).unwrap();
+
+        assert_eq!(event.receiver_address, 0x0010);
+        assert_eq!(event.programmer_address, 0x0001);
+        assert_eq!(event.config_size, 76);
+    }
+
+    #[test]
+    fn wrong_argument_count_is_rejected() {
+        assert!(event_packet(ProgrammerStartConfigUpgrade, vec!["0x0010", "76"]).is_err());
+    }
+}
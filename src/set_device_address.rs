@@ -17,6 +17,7 @@ pub fn set_device_address(
     devices: &BTreeSet<BootloaderHelloEvent>,
     new_address: u16,
     address: u16,
+    dry_run: bool,
 ) -> Result<(), ConfiguratorError> {
     for device in devices.iter() {
         if device.bootloader_address == address {
@@ -31,6 +32,14 @@ pub fn set_device_address(
                 new_address,
             };
 
+            if dry_run {
+                println!(
+                    "Would send ProgrammerSetDeviceAddressEvent (receiver_address: {:#06x}, new_address: {:#06x}).",
+                    address, new_address
+                );
+                return Ok(());
+            }
+
             let _: AckEvent = match protocol.exchange_packet(
                 programmer_set_device_address_event.to_packet(),
                 false,
@@ -0,0 +1,543 @@
+use chrono::{DateTime, Duration, Utc};
+use parse_int::parse;
+use std::fs;
+use std::path::Path;
+
+use ross_config::config::Config;
+use ross_config::serializer::ConfigSerializer;
+use ross_config::state_manager::StateManager;
+use ross_config::Value;
+use ross_protocol::packet::Packet;
+
+use crate::config_file::load_config_data;
+use crate::event_type::EventType;
+use crate::ross_configurator::*;
+use crate::send_event::event_packet;
+
+const REPEAT_DIRECTIVE: &str = "repeat";
+const TIME_DIRECTIVE: &str = "time";
+const WAIT_DIRECTIVE: &str = "wait";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScriptAction {
+    Event(Packet),
+    SetTime(DateTime<Utc>),
+    Wait(Duration),
+}
+
+#[derive(Debug, Clone)]
+pub struct ScriptLine {
+    pub line: usize,
+    pub text: String,
+    pub count: usize,
+    pub action: ScriptAction,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StateChange {
+    pub index: u32,
+    pub old_value: Option<Value>,
+    pub new_value: Option<Value>,
+}
+
+#[derive(Debug, Default)]
+pub struct SimulationStep {
+    pub matched: Vec<usize>,
+    pub state_changes: Vec<StateChange>,
+    pub packets: Vec<Packet>,
+    pub errors: Vec<String>,
+}
+
+pub struct Simulator {
+    config: Config,
+    state_manager: StateManager,
+    device_address: u16,
+}
+
+pub fn simulate(
+    config_path: &Path,
+    script_path: &Path,
+    device_address: u16,
+    env: Option<&str>,
+    require_checksum: bool,
+) -> Result<(), ConfiguratorError> {
+    let config_data = load_config_data(config_path, env, require_checksum)?;
+    let config = ConfigSerializer::deserialize(&config_data)
+        .map_err(ConfiguratorError::ConfigSerializerError)?;
+    let script = load_script(script_path)?;
+
+    let mut simulator = Simulator::new(config, device_address);
+
+    println!(
+        "Simulating {} as device {:#06x}.",
+        config_path.display(),
+        device_address
+    );
+    println!("Initial state: {}.", simulator.describe_state());
+    println!("Clock starts at {}.", simulator.time().to_rfc3339());
+
+    let mut event_count = 0;
+    let mut packet_count = 0;
+    let mut state_change_count = 0;
+    let mut error_count = 0;
+
+    for script_line in script.iter() {
+        println!(
+            "{}:{}: {}",
+            script_path.display(),
+            script_line.line,
+            script_line.text
+        );
+
+        let packet = match &script_line.action {
+            ScriptAction::Event(packet) => packet,
+            ScriptAction::SetTime(time) => {
+                simulator.set_time(*time);
+                println!("    clock set to {}.", simulator.time().to_rfc3339());
+                continue;
+            }
+            ScriptAction::Wait(duration) => {
+                for _ in 0..script_line.count {
+                    simulator.advance(*duration);
+                }
+
+                println!("    clock advanced to {}.", simulator.time().to_rfc3339());
+                continue;
+            }
+        };
+
+        let mut had_effect = false;
+
+        for i in 0..script_line.count {
+            let step = simulator.process(packet);
+
+            event_count += 1;
+            packet_count += step.packets.len();
+            state_change_count += step.state_changes.len();
+            error_count += step.errors.len();
+
+            if step.matched.is_empty() && step.errors.is_empty() {
+                continue;
+            }
+
+            had_effect = true;
+
+            let prefix = if script_line.count > 1 {
+                format!("[{}/{}] ", i + 1, script_line.count)
+            } else {
+                String::new()
+            };
+
+            print_step(&prefix, &step);
+        }
+
+        if !had_effect {
+            println!("    no event processor matched.");
+        }
+    }
+
+    println!("Final state: {}.", simulator.describe_state());
+    println!(
+        "Simulated {} events ({} packets emitted, {} state changes).",
+        event_count, packet_count, state_change_count
+    );
+
+    if error_count != 0 {
+        eprintln!(
+            "{} event processors failed during the simulation.",
+            error_count
+        );
+        return Err(ConfiguratorError::SimulationFailed);
+    }
+
+    Ok(())
+}
+
+pub fn load_script(path: &Path) -> Result<Vec<ScriptLine>, ConfiguratorError> {
+    let text = fs::read_to_string(path).map_err(ConfiguratorError::IOError)?;
+    let mut script = vec![];
+
+    for (i, line) in text.lines().enumerate() {
+        let text = match line.find('#') {
+            Some(position) => line[..position].trim(),
+            None => line.trim(),
+        };
+
+        if text.is_empty() {
+            continue;
+        }
+
+        let mut words: Vec<&str> = text.split_whitespace().collect();
+        let mut count = 1;
+
+        if words[0] == REPEAT_DIRECTIVE {
+            count = match words.get(1).and_then(|word| parse::<usize>(word).ok()) {
+                Some(count) if count > 0 && words.len() > 2 => count,
+                _ => {
+                    eprintln!(
+                        "{}:{}: expected \"{} <count> <event> [data]...\".",
+                        path.display(),
+                        i + 1,
+                        REPEAT_DIRECTIVE
+                    );
+                    return Err(ConfiguratorError::BadUsage);
+                }
+            };
+
+            words.drain(..2);
+        }
+
+        let action = match (words[0], words.get(1)) {
+            (TIME_DIRECTIVE, Some(time)) if words.len() == 2 => {
+                match DateTime::parse_from_rfc3339(time) {
+                    Ok(time) => ScriptAction::SetTime(time.with_timezone(&Utc)),
+                    Err(_) => {
+                        eprintln!(
+                            "{}:{}: expected \"{} <RFC 3339 date and time>\", e.g. \"{} 2024-05-01T18:30:00Z\".",
+                            path.display(),
+                            i + 1,
+                            TIME_DIRECTIVE,
+                            TIME_DIRECTIVE
+                        );
+                        return Err(ConfiguratorError::BadUsage);
+                    }
+                }
+            }
+            (WAIT_DIRECTIVE, Some(seconds)) if words.len() == 2 => match parse::<u32>(seconds) {
+                Ok(seconds) => ScriptAction::Wait(Duration::seconds(seconds as i64)),
+                Err(_) => {
+                    eprintln!(
+                        "{}:{}: expected \"{} <seconds>\".",
+                        path.display(),
+                        i + 1,
+                        WAIT_DIRECTIVE
+                    );
+                    return Err(ConfiguratorError::BadUsage);
+                }
+            },
+            _ => {
+                let event = match words[0].parse::<EventType>() {
+                    Ok(event) => event,
+                    Err(_) => {
+                        eprintln!(
+                            "{}:{}: unknown event \"{}\", expected one of: {}.",
+                            path.display(),
+                            i + 1,
+                            words[0],
+                            EventType::variants().join(", ")
+                        );
+                        return Err(ConfiguratorError::BadUsage);
+                    }
+                };
+
+                match event_packet(event, words[1..].to_vec()) {
+                    Ok(packet) => ScriptAction::Event(packet),
+                    Err(err) => {
+                        eprintln!("{}:{}: invalid event data.", path.display(), i + 1);
+                        return Err(err);
+                    }
+                }
+            }
+        };
+
+        script.push(ScriptLine {
+            line: i + 1,
+            text: text.to_string(),
+            count,
+            action,
+        });
+    }
+
+    Ok(script)
+}
+
+impl Simulator {
+    pub fn new(config: Config, device_address: u16) -> Self {
+        let mut state_manager = StateManager::new();
+
+        for (index, value) in config.initial_state.iter() {
+            state_manager.set_value(*index, value.clone());
+        }
+
+        Self {
+            config,
+            state_manager,
+            device_address,
+        }
+    }
+
+    pub fn process(&mut self, packet: &Packet) -> SimulationStep {
+        let mut step = SimulationStep::default();
+        let old_state = self.state();
+
+        for (i, event_processor) in self.config.event_processors.iter_mut().enumerate() {
+            match event_processor
+                .matcher
+                .do_match(packet, &mut self.state_manager)
+            {
+                Ok(true) => step.matched.push(i),
+                Ok(false) => continue,
+                Err(err) => {
+                    step.errors
+                        .push(format!("event processor #{} failed to match: {:?}", i, err));
+                    continue;
+                }
+            }
+
+            for creator in event_processor.creators.iter_mut() {
+                match creator.create(packet, &mut self.state_manager, self.device_address) {
+                    Ok(Some(packet)) => step.packets.push(packet),
+                    Ok(None) => {}
+                    Err(err) => step.errors.push(format!(
+                        "event processor #{} failed to create a packet: {:?}",
+                        i, err
+                    )),
+                }
+            }
+        }
+
+        for (index, old_value) in old_state.into_iter() {
+            let new_value = self.state_manager.get_value(index).cloned();
+
+            if new_value != old_value {
+                step.state_changes.push(StateChange {
+                    index,
+                    old_value,
+                    new_value,
+                });
+            }
+        }
+
+        step
+    }
+
+    pub fn time(&self) -> DateTime<Utc> {
+        *self.state_manager.get_date_time()
+    }
+
+    pub fn set_time(&mut self, time: DateTime<Utc>) {
+        self.state_manager.set_date_time(time);
+    }
+
+    pub fn advance(&mut self, duration: Duration) {
+        let time = self.time() + duration;
+
+        self.state_manager.set_date_time(time);
+    }
+
+    pub fn state(&self) -> Vec<(u32, Option<Value>)> {
+        self.config
+            .initial_state
+            .keys()
+            .map(|index| (*index, self.state_manager.get_value(*index).cloned()))
+            .collect()
+    }
+
+    fn describe_state(&self) -> String {
+        let state = self.state();
+
+        if state.is_empty() {
+            return "empty".to_string();
+        }
+
+        state
+            .iter()
+            .map(|(index, value)| format!("state {} = {}", index, describe_value(value)))
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+}
+
+fn print_step(prefix: &str, step: &SimulationStep) {
+    for i in step.matched.iter() {
+        println!("    {}event processor #{} matched.", prefix, i);
+    }
+
+    for change in step.state_changes.iter() {
+        println!(
+            "    {}state {} changed from {} to {}.",
+            prefix,
+            change.index,
+            describe_value(&change.old_value),
+            describe_value(&change.new_value)
+        );
+    }
+
+    for packet in step.packets.iter() {
+        println!("    {}emitted {}.", prefix, describe_packet(packet));
+    }
+
+    for error in step.errors.iter() {
+        eprintln!("    {}{}.", prefix, error);
+    }
+}
+
+fn describe_value(value: &Option<Value>) -> String {
+    match value {
+        Some(value) => format!("{:?}", value),
+        None => "nothing".to_string(),
+    }
+}
+
+fn describe_packet(packet: &Packet) -> String {
+    let event = if packet.data.len() >= 2 {
+        let code = u16::from_be_bytes([packet.data[0], packet.data[1]]);
+
+        match EventType::all()
+            .into_iter()
+            .find(|event_type| event_type.code() == code)
+        {
+            Some(event_type) => format!("{} event", event_type),
+            None => format!("event {:#06x}", code),
+        }
+    } else {
+        "packet".to_string()
+    };
+
+    format!("{} ({:?})", event, packet)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::event_type::EventType::*;
+
+    use chrono::TimeZone;
+    use ross_dsl::Parser;
+    use std::env;
+    use std::process;
+
+    const CONFIG: &str = "let light = false;
+        let ticks = 0~u32;
+        do {
+            match event BUTTON_PRESSED_EVENT_CODE;
+            match producer 0x0011~u16;
+            match { ButtonIndexExtractor(); ValueEqualToConstFilter(2~u8); }
+            match { FlipStateFilter(light); }
+            fire { PacketExtractor(); PacketProducer(0x0012~u16); }
+        }
+        do {
+            match tick;
+            match { IncrementStateByConstFilter(ticks, 1~u32); }
+        }";
+
+    fn simulator() -> Simulator {
+        Simulator::new(Parser::parse(CONFIG).unwrap(), 0x0001)
+    }
+
+    fn packet(event: EventType, data: Vec<&str>) -> Packet {
+        event_packet(event, data).unwrap()
+    }
+
+    fn script(name: &str, text: &str) -> Result<Vec<ScriptLine>, ConfiguratorError> {
+        let path = env::temp_dir().join(format!(
+            "ross_configurator_simulate_{}_{}.txt",
+            name,
+            process::id()
+        ));
+        fs::write(&path, text).unwrap();
+
+        let script = load_script(&path);
+        fs::remove_file(&path).unwrap();
+
+        script
+    }
+
+    #[test]
+    fn matching_event_changes_state_and_emits_packets() {
+        let mut simulator = simulator();
+        let step = simulator.process(&packet(ButtonPressed, vec!["0x0001", "0x0011", "2"]));
+
+        assert_eq!(step.matched, vec![0]);
+        assert_eq!(
+            step.state_changes,
+            vec![StateChange {
+                index: 0,
+                old_value: Some(Value::Bool(false)),
+                new_value: Some(Value::Bool(true)),
+            }]
+        );
+        assert_eq!(step.packets.len(), 1);
+        assert_eq!(step.packets[0].device_address, 0x0012);
+        assert!(step.errors.is_empty());
+    }
+
+    #[test]
+    fn filtered_event_has_no_effect() {
+        let mut simulator = simulator();
+        let step = simulator.process(&packet(ButtonPressed, vec!["0x0001", "0x0011", "3"]));
+
+        assert!(step.matched.is_empty());
+        assert!(step.state_changes.is_empty());
+        assert!(step.packets.is_empty());
+        assert_eq!(
+            simulator.state(),
+            vec![(0, Some(Value::Bool(false))), (1, Some(Value::U32(0)))]
+        );
+    }
+
+    #[test]
+    fn state_carries_over_between_events() {
+        let mut simulator = simulator();
+
+        for _ in 0..3 {
+            simulator.process(&packet(SystemTick, vec!["0x0001"]));
+        }
+
+        assert_eq!(simulator.state()[1], (1, Some(Value::U32(3))));
+    }
+
+    #[test]
+    fn clock_can_be_set_and_advanced() {
+        let mut simulator = simulator();
+        let time = Utc.ymd(2024, 5, 1).and_hms(18, 30, 0);
+
+        simulator.set_time(time);
+        simulator.advance(Duration::seconds(90));
+
+        assert_eq!(simulator.time(), Utc.ymd(2024, 5, 1).and_hms(18, 31, 30));
+    }
+
+    #[test]
+    fn script_supports_comments_repeat_and_clock_directives() {
+        let script = script(
+            "valid",
+            "# comment\n\
+             \n\
+             ButtonPressed 0x0001 0x0011 2  # trailing comment\n\
+             repeat 3 SystemTick 0x0001\n\
+             time 2024-05-01T20:30:00+02:00\n\
+             repeat 2 wait 30\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            script
+                .iter()
+                .map(|line| (line.line, line.count))
+                .collect::<Vec<_>>(),
+            vec![(3, 1), (4, 3), (5, 1), (6, 2)]
+        );
+        assert_eq!(script[0].text, "ButtonPressed 0x0001 0x0011 2");
+        assert_eq!(
+            script[1].action,
+            ScriptAction::Event(packet(SystemTick, vec!["0x0001"]))
+        );
+        assert_eq!(
+            script[2].action,
+            ScriptAction::SetTime(Utc.ymd(2024, 5, 1).and_hms(18, 30, 0))
+        );
+        assert_eq!(script[3].action, ScriptAction::Wait(Duration::seconds(30)));
+    }
+
+    #[test]
+    fn invalid_script_lines_are_rejected() {
+        assert!(script("unknown", "NoSuchEvent 0x0001\n").is_err());
+        assert!(script("repeat_zero", "repeat 0 SystemTick 0x0001\n").is_err());
+        assert!(script("repeat_alone", "repeat 2\n").is_err());
+        assert!(script("bad_data", "ButtonPressed 0x0001\n").is_err());
+        assert!(script("bad_time", "time tomorrow\n").is_err());
+        assert!(script("bad_wait", "wait -5\n").is_err());
+    }
+}
@@ -0,0 +1,169 @@
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::ross_configurator::*;
+
+#[derive(Debug, Clone)]
+pub struct TransferStats {
+    pub address: u16,
+    pub size: usize,
+    pub sent_bytes: usize,
+    pub chunks: usize,
+    pub restarts: usize,
+    pub failed: bool,
+    pub duration: Duration,
+    pub exchange_times: Vec<Duration>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TransferReport {
+    pub address: u16,
+    pub size: usize,
+    pub sent_bytes: usize,
+    pub chunks: usize,
+    pub restarts: usize,
+    pub failed: bool,
+    pub duration_ms: f64,
+    pub ack_wait_ms: f64,
+    pub min_exchange_ms: f64,
+    pub avg_exchange_ms: f64,
+    pub max_exchange_ms: f64,
+    pub p95_exchange_ms: f64,
+    pub throughput: f64,
+}
+
+impl TransferStats {
+    pub fn new(address: u16, size: usize) -> Self {
+        Self {
+            address,
+            size,
+            sent_bytes: 0,
+            chunks: 0,
+            restarts: 0,
+            failed: false,
+            duration: Duration::from_secs(0),
+            exchange_times: vec![],
+        }
+    }
+
+    pub fn record_chunk(&mut self, size: usize, exchange_time: Duration) {
+        self.sent_bytes += size;
+        self.chunks += 1;
+        self.exchange_times.push(exchange_time);
+    }
+
+    pub fn report(&self) -> TransferReport {
+        let mut exchange_times: Vec<f64> = self
+            .exchange_times
+            .iter()
+            .map(|exchange_time| exchange_time.as_secs_f64() * 1000.0)
+            .collect();
+        exchange_times.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        let (min, avg, max, p95) = if exchange_times.is_empty() {
+            (0.0, 0.0, 0.0, 0.0)
+        } else {
+            let p95_index = ((exchange_times.len() as f64 * 0.95).ceil() as usize).max(1) - 1;
+
+            (
+                exchange_times[0],
+                exchange_times.iter().sum::<f64>() / exchange_times.len() as f64,
+                exchange_times[exchange_times.len() - 1],
+                exchange_times[p95_index],
+            )
+        };
+
+        let seconds = self.duration.as_secs_f64();
+
+        TransferReport {
+            address: self.address,
+            size: self.size,
+            sent_bytes: self.sent_bytes,
+            chunks: self.chunks,
+            restarts: self.restarts,
+            failed: self.failed,
+            duration_ms: seconds * 1000.0,
+            ack_wait_ms: PACKET_TIMEOUT_MS as f64,
+            min_exchange_ms: min,
+            avg_exchange_ms: avg,
+            max_exchange_ms: max,
+            p95_exchange_ms: p95,
+            throughput: if seconds > 0.0 {
+                self.sent_bytes as f64 / seconds
+            } else {
+                0.0
+            },
+        }
+    }
+}
+
+impl TransferReport {
+    pub fn print(&self) {
+        println!(
+            "Transferred {} bytes to device (address: {:#06x}) in {} chunks ({} bytes sent, {} restarts).",
+            self.size, self.address, self.chunks, self.sent_bytes, self.restarts
+        );
+        println!(
+            "Chunk exchange time (serial write and buffered ACK read, excluding the fixed {:.0} ms wait): min {:.1} ms, avg {:.1} ms, max {:.1} ms, p95 {:.1} ms.",
+            self.ack_wait_ms,
+            self.min_exchange_ms,
+            self.avg_exchange_ms,
+            self.max_exchange_ms,
+            self.p95_exchange_ms
+        );
+        println!(
+            "Effective throughput: {:.0} bytes/s over {:.1} s.",
+            self.throughput,
+            self.duration_ms / 1000.0
+        );
+    }
+}
+
+pub fn write_stats_file(path: &Path, reports: &[TransferReport]) -> Result<(), ConfiguratorError> {
+    let string = serde_json::to_string_pretty(reports).map_err(ConfiguratorError::JsonError)?;
+
+    fs::write(path, string).map_err(ConfiguratorError::IOError)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_summarizes_exchange_times() {
+        let mut stats = TransferStats::new(0x0011, 300);
+
+        for exchange_ms in 1..=20 {
+            stats.record_chunk(15, Duration::from_millis(exchange_ms));
+        }
+        stats.restarts = 2;
+        stats.duration = Duration::from_secs(2);
+
+        let report = stats.report();
+
+        assert_eq!(report.chunks, 20);
+        assert_eq!(report.sent_bytes, 300);
+        assert_eq!(report.restarts, 2);
+        assert!(!report.failed);
+        assert_eq!(report.min_exchange_ms, 1.0);
+        assert_eq!(report.max_exchange_ms, 20.0);
+        assert_eq!(report.avg_exchange_ms, 10.5);
+        assert_eq!(report.p95_exchange_ms, 19.0);
+        assert_eq!(report.throughput, 150.0);
+    }
+
+    #[test]
+    fn report_of_an_empty_transfer_has_no_exchange_times() {
+        let mut stats = TransferStats::new(0x0011, 300);
+        stats.failed = true;
+
+        let report = stats.report();
+
+        assert!(report.failed);
+        assert_eq!(report.chunks, 0);
+        assert_eq!(report.p95_exchange_ms, 0.0);
+        assert_eq!(report.throughput, 0.0);
+    }
+}
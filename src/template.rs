@@ -0,0 +1,248 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fs;
+use std::path::Path;
+
+use ross_config::config::Config;
+use ross_config::serializer::ConfigSerializer;
+
+use crate::config_file::load_template;
+use crate::ross_configurator::*;
+
+pub const ADDRESS_PARAMETER: &str = "address";
+
+#[derive(Deserialize)]
+struct ParameterTable {
+    #[serde(default)]
+    defaults: BTreeMap<String, toml::Value>,
+    devices: Vec<BTreeMap<String, toml::Value>>,
+}
+
+pub struct DeviceParameters {
+    pub address: u16,
+    pub parameters: BTreeMap<String, String>,
+}
+
+pub fn load_parameters(path: &Path) -> Result<Vec<DeviceParameters>, ConfiguratorError> {
+    let string = fs::read_to_string(path).map_err(ConfiguratorError::IOError)?;
+    let table: ParameterTable = if is_yaml(path) {
+        serde_yaml::from_str(&string).map_err(ConfiguratorError::YamlError)?
+    } else {
+        toml::from_str(&string).map_err(ConfiguratorError::TomlError)?
+    };
+
+    let mut devices: Vec<DeviceParameters> = vec![];
+
+    for (i, device) in table.devices.into_iter().enumerate() {
+        let mut values = table.defaults.clone();
+        values.extend(device);
+
+        let address = match values.get(ADDRESS_PARAMETER) {
+            Some(toml::Value::Integer(address)) => u16::try_from(*address).ok(),
+            _ => None,
+        };
+
+        let address = match address {
+            Some(address) => address,
+            None => {
+                eprintln!(
+                    "Device #{} in {} has no valid \"{}\" parameter.",
+                    i,
+                    path.display(),
+                    ADDRESS_PARAMETER
+                );
+                return Err(ConfiguratorError::BadUsage);
+            }
+        };
+
+        if devices.iter().any(|device| device.address == address) {
+            eprintln!(
+                "Device (address: {:#06x}) is listed more than once in {}.",
+                address,
+                path.display()
+            );
+            return Err(ConfiguratorError::BadUsage);
+        }
+
+        let mut parameters = BTreeMap::new();
+
+        for (name, value) in values.into_iter() {
+            let value = match value {
+                toml::Value::Integer(value) => value.to_string(),
+                toml::Value::Boolean(value) => value.to_string(),
+                toml::Value::String(value) => value,
+                _ => {
+                    eprintln!(
+                        "Parameter \"{}\" of device (address: {:#06x}) is not an integer, boolean or string.",
+                        name, address
+                    );
+                    return Err(ConfiguratorError::BadUsage);
+                }
+            };
+
+            parameters.insert(name, value);
+        }
+
+        devices.push(DeviceParameters {
+            address,
+            parameters,
+        });
+    }
+
+    Ok(devices)
+}
+
+fn is_yaml(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| YAML_PARAMETER_EXTENSIONS.contains(&extension))
+}
+
+pub fn render_configs(
+    template_path: &Path,
+    devices: &[DeviceParameters],
+    env: Option<&str>,
+) -> Result<BTreeMap<u16, Config>, ConfiguratorError> {
+    let mut configs = BTreeMap::new();
+
+    for device in devices.iter() {
+        println!(
+            "Rendering config template for device (address: {:#06x}).",
+            device.address
+        );
+
+        configs.insert(
+            device.address,
+            load_template(template_path, &device.parameters, env)?,
+        );
+    }
+
+    Ok(configs)
+}
+
+pub fn render_config_data(
+    template_path: &Path,
+    parameters_path: &Path,
+    env: Option<&str>,
+) -> Result<BTreeMap<u16, Vec<u8>>, ConfiguratorError> {
+    let devices = load_parameters(parameters_path)?;
+    let mut config_data = BTreeMap::new();
+
+    for (address, config) in render_configs(template_path, &devices, env)?.into_iter() {
+        config_data.insert(
+            address,
+            ConfigSerializer::serialize(&config)
+                .map_err(ConfiguratorError::ConfigSerializerError)?,
+        );
+    }
+
+    Ok(config_data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+    use std::process;
+
+    fn load(name: &str, text: &str) -> Result<Vec<DeviceParameters>, ConfiguratorError> {
+        let path = env::temp_dir().join(format!(
+            "ross_configurator_template_{}_{}",
+            process::id(),
+            name
+        ));
+        fs::write(&path, text).unwrap();
+
+        let devices = load_parameters(&path);
+        fs::remove_file(&path).unwrap();
+
+        devices
+    }
+
+    fn summary(devices: &[DeviceParameters]) -> Vec<(u16, Vec<(&str, &str)>)> {
+        devices
+            .iter()
+            .map(|device| {
+                (
+                    device.address,
+                    device
+                        .parameters
+                        .iter()
+                        .map(|(name, value)| (name.as_str(), value.as_str()))
+                        .collect(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn toml_and_yaml_tables_load_the_same_parameters() {
+        let toml = load(
+            "params.toml",
+            "[defaults]\n\
+             channel = 1\n\
+             \n\
+             [[devices]]\n\
+             address = 0x0010\n\
+             room = \"hallway\"\n\
+             \n\
+             [[devices]]\n\
+             address = 0x0011\n\
+             channel = 2\n\
+             dimmable = true\n",
+        )
+        .unwrap();
+        let yaml = load(
+            "params.yaml",
+            "defaults:\n\
+             \x20 channel: 1\n\
+             devices:\n\
+             \x20 - address: 0x0010\n\
+             \x20   room: hallway\n\
+             \x20 - address: 0x0011\n\
+             \x20   channel: 2\n\
+             \x20   dimmable: true\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            summary(&toml),
+            vec![
+                (
+                    0x0010,
+                    vec![("address", "16"), ("channel", "1"), ("room", "hallway")]
+                ),
+                (
+                    0x0011,
+                    vec![("address", "17"), ("channel", "2"), ("dimmable", "true")]
+                ),
+            ]
+        );
+        assert_eq!(summary(&yaml), summary(&toml));
+    }
+
+    #[test]
+    fn yml_extension_is_read_as_yaml() {
+        let devices = load("params.yml", "devices:\n  - address: 18\n").unwrap();
+
+        assert_eq!(devices[0].address, 18);
+    }
+
+    #[test]
+    fn invalid_tables_are_rejected() {
+        assert!(load("no_address.yaml", "devices:\n  - room: hallway\n").is_err());
+        assert!(load(
+            "duplicate.yaml",
+            "devices:\n  - address: 1\n  - address: 1\n"
+        )
+        .is_err());
+        assert!(load(
+            "nested.yaml",
+            "devices:\n  - address: 1\n    room: [a, b]\n"
+        )
+        .is_err());
+        assert!(load("malformed.yaml", "devices: [\n").is_err());
+    }
+}
@@ -0,0 +1,621 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use ross_protocol::convert_packet::ConvertPacket;
+use ross_protocol::event::general::*;
+use ross_protocol::event::programmer::*;
+use ross_protocol::interface::serial::Serial;
+use ross_protocol::protocol::{Protocol, ProtocolError};
+
+use crate::cancellation::CancellationToken;
+use crate::inventory::ChunkSize;
+use crate::ross_configurator::*;
+use crate::stats::{TransferReport, TransferStats};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransferKind {
+    Firmware,
+    Config,
+}
+
+impl fmt::Display for TransferKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TransferKind::Firmware => write!(f, "firmware"),
+            TransferKind::Config => write!(f, "config"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransferProgress {
+    pub kind: TransferKind,
+    pub hash: String,
+    pub size: usize,
+    pub acked_offset: usize,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct TransferJournal {
+    #[serde(skip)]
+    path: PathBuf,
+    pub transfers: BTreeMap<u16, TransferProgress>,
+}
+
+impl TransferJournal {
+    pub fn default_path() -> PathBuf {
+        data_directory().join(TRANSFER_JOURNAL_FILE_NAME)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, ConfiguratorError> {
+        let mut journal = match fs::read_to_string(path) {
+            Ok(string) => serde_json::from_str(&string).map_err(ConfiguratorError::JsonError)?,
+            Err(err) if err.kind() == ErrorKind::NotFound => Self::default(),
+            Err(err) => return Err(ConfiguratorError::IOError(err)),
+        };
+
+        journal.path = path.to_path_buf();
+
+        Ok(journal)
+    }
+
+    pub fn save(&self) -> Result<(), ConfiguratorError> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).map_err(ConfiguratorError::IOError)?;
+        }
+
+        let string = serde_json::to_string_pretty(self).map_err(ConfiguratorError::JsonError)?;
+        let temp_path = self.path.with_extension("json.tmp");
+
+        fs::write(&temp_path, string).map_err(ConfiguratorError::IOError)?;
+        fs::rename(&temp_path, &self.path).map_err(ConfiguratorError::IOError)
+    }
+
+    // The journal only helps resuming, so failing to save it must not abort a transfer.
+    pub fn save_or_warn(&self) {
+        if let Err(err) = self.save() {
+            eprintln!(
+                "Could not save the transfer journal {}, continuing without it: {:?}",
+                self.path.display(),
+                err
+            );
+        }
+    }
+
+    pub fn get(&self, address: u16) -> Option<&TransferProgress> {
+        self.transfers.get(&address)
+    }
+
+    pub fn interrupted(&self, kind: TransferKind) -> Vec<u16> {
+        self.transfers
+            .iter()
+            .filter(|(_, progress)| progress.kind == kind)
+            .map(|(address, _)| *address)
+            .collect()
+    }
+
+    pub fn check_resume(
+        &self,
+        address: u16,
+        kind: TransferKind,
+        data: &[u8],
+    ) -> Result<(), ConfiguratorError> {
+        let progress = match self.get(address) {
+            Some(progress) if progress.kind == kind => progress,
+            _ => return Ok(()),
+        };
+
+        if progress.hash != image_hash(data) {
+            eprintln!(
+                "Device (address: {:#06x}) was interrupted while receiving a different {} image (sha256: {}).",
+                address, kind, progress.hash
+            );
+            return Err(ConfiguratorError::TransferMismatch);
+        }
+
+        println!(
+            "Resuming {} transfer to device (address: {:#06x}), previously interrupted after {} of {} bytes.",
+            kind, address, progress.acked_offset, progress.size
+        );
+        println!("The device cannot continue a partial transfer, restarting from byte 0.");
+
+        Ok(())
+    }
+
+    pub fn print_interrupted(&self) {
+        for (address, progress) in self.transfers.iter() {
+            eprintln!(
+                "Device (address: {:#06x}) was left mid-upgrade ({} {} of {} bytes) and needs re-flashing.",
+                address, progress.kind, progress.acked_offset, progress.size
+            );
+        }
+    }
+}
+
+pub fn image_hash(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+pub struct TransferOptions<'a> {
+    pub chunk_size: ChunkSize,
+    pub cancellation: &'a CancellationToken,
+    pub dry_run: bool,
+    pub reports: &'a RefCell<Vec<TransferReport>>,
+}
+
+pub fn transfer(
+    protocol: &mut Protocol<Serial>,
+    programmer: &ProgrammerHelloEvent,
+    address: u16,
+    kind: TransferKind,
+    data: &[u8],
+    journal: &mut TransferJournal,
+    options: &TransferOptions,
+) -> Result<TransferStats, ConfiguratorError> {
+    if options.cancellation.is_cancelled() {
+        return Err(ConfiguratorError::Cancelled { address, offset: 0 });
+    }
+
+    if options.dry_run {
+        print_transfer_plan(address, kind, data, options.chunk_size);
+        return Ok(TransferStats::new(address, data.len()));
+    }
+
+    let mut upload = Upload {
+        protocol,
+        programmer,
+        address,
+        kind,
+        data,
+        journal,
+        cancellation: options.cancellation,
+        stats: TransferStats::new(address, data.len()),
+    };
+
+    let start = Instant::now();
+
+    let result = upload.send(options.chunk_size);
+
+    let mut stats = upload.stats;
+    stats.duration = start.elapsed();
+    stats.failed = result.is_err();
+
+    let report = stats.report();
+
+    match result {
+        Ok(()) => {
+            report.print();
+            options.reports.borrow_mut().push(report);
+
+            journal.transfers.remove(&address);
+            journal.save_or_warn();
+
+            Ok(stats)
+        }
+        Err(err) => {
+            options.reports.borrow_mut().push(report);
+
+            if let Some(progress) = journal.get(address) {
+                eprintln!(
+                    "Device (address: {:#06x}) was left mid-upgrade after {} of {} bytes and needs re-flashing (use --resume).",
+                    address, progress.acked_offset, progress.size
+                );
+            }
+
+            Err(err)
+        }
+    }
+}
+
+fn print_transfer_plan(address: u16, kind: TransferKind, data: &[u8], chunk_size: ChunkSize) {
+    let (event_name, size_name) = match kind {
+        TransferKind::Firmware => ("ProgrammerStartFirmwareUpgradeEvent", "firmware_size"),
+        TransferKind::Config => ("ProgrammerStartConfigUpgradeEvent", "config_size"),
+    };
+
+    println!(
+        "Would send {} (receiver_address: {:#06x}, {}: {}).",
+        event_name,
+        address,
+        size_name,
+        data.len()
+    );
+
+    let chunk_size = match chunk_size {
+        ChunkSize::Fixed(chunk_size) => chunk_size,
+        ChunkSize::Adaptive => {
+            println!(
+                "Adaptive chunk size starts at {} bytes, restarts the transfer with half the chunk size after an error and grows back after a run of successes.",
+                ADAPTIVE_INITIAL_CHUNK_SIZE
+            );
+            ADAPTIVE_INITIAL_CHUNK_SIZE
+        }
+    };
+
+    println!(
+        "Would send {} DataEvent chunks of up to {} bytes (receiver_address: {:#06x}).",
+        data.chunks(chunk_size).count(),
+        chunk_size,
+        address
+    );
+
+    for (i, chunk) in data.chunks(chunk_size).enumerate() {
+        let slice_start = i * chunk_size;
+
+        println!(
+            "Would send bytes {} - {}",
+            slice_start,
+            slice_start + chunk.len()
+        );
+    }
+}
+
+struct Upload<'a, 'p> {
+    protocol: &'a mut Protocol<'p, Serial>,
+    programmer: &'a ProgrammerHelloEvent,
+    address: u16,
+    kind: TransferKind,
+    data: &'a [u8],
+    journal: &'a mut TransferJournal,
+    cancellation: &'a CancellationToken,
+    stats: TransferStats,
+}
+
+impl<'a, 'p> Upload<'a, 'p> {
+    fn send(&mut self, chunk_size: ChunkSize) -> Result<(), ConfiguratorError> {
+        let mut chunk_sizer = ChunkSizer::new(chunk_size);
+
+        loop {
+            match self.send_attempt(&mut chunk_sizer) {
+                Err(ConfiguratorError::ProtocolError(err)) => {
+                    if !chunk_sizer.failed() {
+                        return Err(ConfiguratorError::ProtocolError(err));
+                    }
+
+                    self.stats.restarts += 1;
+
+                    eprintln!(
+                        "Restarting transfer to device (address: {:#06x}) from byte 0 with {} byte chunks.",
+                        self.address, chunk_sizer.chunk_size
+                    );
+                }
+                result => return result,
+            }
+        }
+    }
+
+    // A DataEvent carries no offset, so a chunk whose ACK was lost cannot be resent without
+    // risking a duplicate write; every failure restarts the transfer with a new start event.
+    fn send_attempt(&mut self, chunk_sizer: &mut ChunkSizer) -> Result<(), ConfiguratorError> {
+        let address = self.address;
+
+        let start_packet = match self.kind {
+            TransferKind::Firmware => ProgrammerStartFirmwareUpgradeEvent {
+                programmer_address: self.programmer.programmer_address,
+                receiver_address: address,
+                firmware_size: self.data.len() as u32,
+            }
+            .to_packet(),
+            TransferKind::Config => ProgrammerStartConfigUpgradeEvent {
+                programmer_address: self.programmer.programmer_address,
+                receiver_address: address,
+                config_size: self.data.len() as u32,
+            }
+            .to_packet(),
+        };
+
+        let _: AckEvent = match self.protocol.exchange_packet(start_packet, false, || {
+            sleep(Duration::from_millis(PACKET_TIMEOUT_MS))
+        }) {
+            Ok(event) => event,
+            Err(err) => return Err(ConfiguratorError::ProtocolError(err)),
+        };
+
+        self.journal.transfers.insert(
+            address,
+            TransferProgress {
+                kind: self.kind,
+                hash: image_hash(self.data),
+                size: self.data.len(),
+                acked_offset: 0,
+            },
+        );
+        self.journal.save_or_warn();
+
+        let mut offset = 0;
+        let mut saved_offset = 0;
+
+        while offset < self.data.len() {
+            if self.cancellation.is_cancelled() {
+                eprintln!(
+                    "Cancelled transfer to device (address: {:#06x}) after {} confirmed bytes.",
+                    address, offset
+                );
+                return Err(ConfiguratorError::Cancelled { address, offset });
+            }
+
+            let chunk_end = (offset + chunk_sizer.chunk_size).min(self.data.len());
+            let chunk = &self.data[offset..chunk_end];
+
+            println!("Sending bytes {} - {}", offset, chunk_end);
+
+            let exchange_time = match self.send_chunk(chunk) {
+                Ok(exchange_time) => exchange_time,
+                Err(err) => {
+                    eprintln!(
+                        "Sending bytes {} - {} failed with error {:?}.",
+                        offset, chunk_end, err
+                    );
+                    return Err(ConfiguratorError::ProtocolError(err));
+                }
+            };
+
+            self.stats.record_chunk(chunk.len(), exchange_time);
+            offset = chunk_end;
+
+            if let Some(progress) = self.journal.transfers.get_mut(&address) {
+                progress.acked_offset = offset;
+            }
+
+            if offset - saved_offset >= JOURNAL_SAVE_INTERVAL_BYTES {
+                self.journal.save_or_warn();
+                saved_offset = offset;
+            }
+
+            if chunk_sizer.succeeded() {
+                println!("Growing chunk size to {} bytes.", chunk_sizer.chunk_size);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn send_chunk(&mut self, chunk: &[u8]) -> Result<Duration, ProtocolError> {
+        let data_event = DataEvent {
+            transmitter_address: self.programmer.programmer_address,
+            receiver_address: self.address,
+            data_len: chunk.len() as u16,
+            data: chunk.to_vec(),
+        };
+
+        let waited = Cell::new(Duration::from_secs(0));
+        let exchange_start = Instant::now();
+
+        let _: AckEvent = self
+            .protocol
+            .exchange_packet(data_event.to_packet(), false, || {
+                let wait_start = Instant::now();
+                sleep(Duration::from_millis(PACKET_TIMEOUT_MS));
+                waited.set(wait_start.elapsed());
+            })?;
+
+        Ok(exchange_start.elapsed().saturating_sub(waited.get()))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct ChunkSizer {
+    chunk_size: usize,
+    max_chunk_size: usize,
+    adaptive: bool,
+    failures: usize,
+    successes: usize,
+}
+
+impl ChunkSizer {
+    fn new(chunk_size: ChunkSize) -> Self {
+        let (chunk_size, adaptive) = match chunk_size {
+            ChunkSize::Fixed(chunk_size) => (chunk_size, false),
+            ChunkSize::Adaptive => (ADAPTIVE_INITIAL_CHUNK_SIZE, true),
+        };
+
+        Self {
+            chunk_size,
+            max_chunk_size: chunk_size,
+            adaptive,
+            failures: 0,
+            successes: 0,
+        }
+    }
+
+    fn succeeded(&mut self) -> bool {
+        self.successes += 1;
+
+        if !self.adaptive
+            || self.successes < ADAPTIVE_GROW_SUCCESSES
+            || self.chunk_size >= self.max_chunk_size
+        {
+            return false;
+        }
+
+        self.chunk_size = (self.chunk_size * 2).min(self.max_chunk_size);
+        self.successes = 0;
+
+        true
+    }
+
+    // Returns whether the transfer should be restarted. Adaptive transfers halve the chunk size
+    // and never grow back to a size that failed, so the number of restarts stays bounded.
+    fn failed(&mut self) -> bool {
+        self.successes = 0;
+
+        if self.adaptive && self.chunk_size > ADAPTIVE_MIN_CHUNK_SIZE {
+            self.chunk_size = (self.chunk_size / 2).max(ADAPTIVE_MIN_CHUNK_SIZE);
+            self.max_chunk_size = self.chunk_size;
+            return true;
+        }
+
+        self.failures += 1;
+
+        self.failures <= TRANSFER_RESTART_LIMIT
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+    use std::process;
+
+    fn journal_path(name: &str) -> PathBuf {
+        env::temp_dir()
+            .join(format!("ross_configurator_test_{}", process::id()))
+            .join(name)
+    }
+
+    #[test]
+    fn journal_round_trips_through_save() -> Result<(), ConfiguratorError> {
+        let path = journal_path("round_trip.json");
+        let mut journal = TransferJournal::load(&path)?;
+
+        journal.transfers.insert(
+            0x0011,
+            TransferProgress {
+                kind: TransferKind::Config,
+                hash: image_hash(&[1, 2, 3]),
+                size: 3,
+                acked_offset: 2,
+            },
+        );
+        journal.save()?;
+
+        let loaded = TransferJournal::load(&path)?;
+
+        assert_eq!(loaded.interrupted(TransferKind::Config), vec![0x0011]);
+        assert!(loaded.interrupted(TransferKind::Firmware).is_empty());
+        assert_eq!(loaded.get(0x0011).unwrap().acked_offset, 2);
+        assert!(!path.with_extension("json.tmp").exists());
+
+        fs::remove_dir_all(path.parent().unwrap()).map_err(ConfiguratorError::IOError)
+    }
+
+    #[test]
+    fn failing_save_only_warns() {
+        let directory = env::temp_dir().join(format!(
+            "ross_configurator_test_not_a_directory_{}",
+            process::id()
+        ));
+        fs::write(&directory, "").unwrap();
+
+        let journal = TransferJournal {
+            path: directory.join("journal.json"),
+            transfers: BTreeMap::new(),
+        };
+
+        assert!(journal.save().is_err());
+        journal.save_or_warn();
+
+        fs::remove_file(&directory).unwrap();
+    }
+
+    #[test]
+    fn fixed_chunk_size_restarts_a_limited_number_of_times() {
+        let mut chunk_sizer = ChunkSizer::new(ChunkSize::Fixed(64));
+
+        for _ in 0..TRANSFER_RESTART_LIMIT {
+            assert!(chunk_sizer.failed());
+            assert_eq!(chunk_sizer.chunk_size, 64);
+            assert!(!chunk_sizer.succeeded());
+        }
+
+        assert!(!chunk_sizer.failed());
+    }
+
+    #[test]
+    fn adaptive_chunk_size_halves_on_every_failure() {
+        let mut chunk_sizer = ChunkSizer::new(ChunkSize::Adaptive);
+
+        assert!(chunk_sizer.failed());
+        assert_eq!(chunk_sizer.chunk_size, ADAPTIVE_INITIAL_CHUNK_SIZE / 2);
+        assert!(chunk_sizer.failed());
+        assert_eq!(chunk_sizer.chunk_size, ADAPTIVE_INITIAL_CHUNK_SIZE / 4);
+    }
+
+    #[test]
+    fn adaptive_chunk_size_gives_up_at_the_minimum() {
+        let mut chunk_sizer = ChunkSizer::new(ChunkSize::Adaptive);
+
+        while chunk_sizer.chunk_size > ADAPTIVE_MIN_CHUNK_SIZE {
+            assert!(chunk_sizer.failed());
+        }
+
+        for _ in 0..TRANSFER_RESTART_LIMIT {
+            assert!(chunk_sizer.failed());
+        }
+
+        assert!(!chunk_sizer.failed());
+        assert_eq!(chunk_sizer.chunk_size, ADAPTIVE_MIN_CHUNK_SIZE);
+    }
+
+    #[test]
+    fn adaptive_chunk_size_grows_back_but_not_to_a_failed_size() {
+        let mut chunk_sizer = ChunkSizer::new(ChunkSize::Adaptive);
+
+        chunk_sizer.failed();
+        chunk_sizer.failed();
+        assert_eq!(chunk_sizer.chunk_size, ADAPTIVE_INITIAL_CHUNK_SIZE / 4);
+        assert_eq!(chunk_sizer.max_chunk_size, ADAPTIVE_INITIAL_CHUNK_SIZE / 4);
+
+        for _ in 0..ADAPTIVE_GROW_SUCCESSES {
+            assert!(!chunk_sizer.succeeded());
+        }
+
+        assert_eq!(chunk_sizer.chunk_size, ADAPTIVE_INITIAL_CHUNK_SIZE / 4);
+    }
+
+    #[test]
+    fn adaptive_chunk_size_grows_up_to_the_initial_size() {
+        let mut chunk_sizer = ChunkSizer::new(ChunkSize::Adaptive);
+        chunk_sizer.chunk_size = ADAPTIVE_INITIAL_CHUNK_SIZE / 2;
+
+        for _ in 0..ADAPTIVE_GROW_SUCCESSES - 1 {
+            assert!(!chunk_sizer.succeeded());
+        }
+
+        assert!(chunk_sizer.succeeded());
+        assert_eq!(chunk_sizer.chunk_size, ADAPTIVE_INITIAL_CHUNK_SIZE);
+
+        for _ in 0..ADAPTIVE_GROW_SUCCESSES {
+            assert!(!chunk_sizer.succeeded());
+        }
+    }
+
+    #[test]
+    fn resume_rejects_a_different_image() {
+        let mut journal = TransferJournal::default();
+
+        journal.transfers.insert(
+            0x0011,
+            TransferProgress {
+                kind: TransferKind::Firmware,
+                hash: image_hash(&[1, 2, 3]),
+                size: 3,
+                acked_offset: 1,
+            },
+        );
+
+        assert!(journal
+            .check_resume(0x0011, TransferKind::Firmware, &[1, 2, 3])
+            .is_ok());
+        assert!(matches!(
+            journal.check_resume(0x0011, TransferKind::Firmware, &[4, 5, 6]),
+            Err(ConfiguratorError::TransferMismatch)
+        ));
+        assert!(journal
+            .check_resume(0x0011, TransferKind::Config, &[4, 5, 6])
+            .is_ok());
+    }
+}
@@ -0,0 +1,71 @@
+use std::collections::BTreeSet;
+use std::time::Duration;
+
+use ross_protocol::event::bootloader::BootloaderHelloEvent;
+use ross_protocol::event::programmer::ProgrammerHelloEvent;
+use ross_protocol::interface::serial::Serial;
+use ross_protocol::protocol::Protocol;
+
+use crate::cancellation::CancellationToken;
+use crate::get_devices::discover_devices;
+use crate::ross_configurator::*;
+
+pub fn verify_device(
+    protocol: &mut Protocol<Serial>,
+    programmer: &ProgrammerHelloEvent,
+    known_devices: &BTreeSet<BootloaderHelloEvent>,
+    address: u16,
+    delay: Duration,
+    cancellation: &CancellationToken,
+) -> Result<(), ConfiguratorError> {
+    println!(
+        "Verifying device (address: {:#06x}) in {} ms.",
+        address,
+        delay.as_millis()
+    );
+
+    if !cancellation.sleep(delay) {
+        eprintln!(
+            "Cancelled verification of device (address: {:#06x}).",
+            address
+        );
+        return Err(ConfiguratorError::WaitCancelled);
+    }
+
+    let devices = discover_devices(protocol, programmer)?;
+
+    if devices
+        .iter()
+        .any(|device| device.bootloader_address == address)
+    {
+        println!("Device (address: {:#06x}) answered again.", address);
+        return Ok(());
+    }
+
+    let unexpected_addresses: Vec<u16> = devices
+        .iter()
+        .map(|device| device.bootloader_address)
+        .filter(|new_address| {
+            !known_devices
+                .iter()
+                .any(|device| device.bootloader_address == *new_address)
+        })
+        .collect();
+
+    if unexpected_addresses.is_empty() {
+        eprintln!(
+            "Device (address: {:#06x}) disappeared after the upgrade.",
+            address
+        );
+        return Err(ConfiguratorError::DeviceNotRediscovered);
+    }
+
+    for new_address in unexpected_addresses.iter() {
+        eprintln!(
+            "Device (address: {:#06x}) did not answer, but an unexpected device (address: {:#06x}) appeared.",
+            address, new_address
+        );
+    }
+
+    Err(ConfiguratorError::DeviceAddressChanged)
+}
@@ -0,0 +1,83 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::cancellation::CancellationToken;
+use crate::preprocessor::{aliases_path, overlay_path, preprocess, Definitions};
+use crate::ross_configurator::*;
+
+pub struct ConfigWatcher {
+    modified: BTreeMap<PathBuf, Option<SystemTime>>,
+}
+
+impl ConfigWatcher {
+    pub fn new(paths: Vec<PathBuf>) -> Self {
+        Self {
+            modified: paths
+                .into_iter()
+                .map(|path| {
+                    let modified = modified_time(&path);
+
+                    (path, modified)
+                })
+                .collect(),
+        }
+    }
+
+    pub fn changed_paths(&self) -> Vec<&Path> {
+        self.modified
+            .iter()
+            .filter(|(path, modified)| modified_time(path) != **modified)
+            .map(|(path, _)| path.as_path())
+            .collect()
+    }
+
+    pub fn wait_for_change(&self, cancellation: &CancellationToken) -> bool {
+        println!(
+            "Watching {} file{} for changes, press Ctrl-C to stop.",
+            self.modified.len(),
+            if self.modified.len() == 1 { "" } else { "s" }
+        );
+
+        while !cancellation.is_cancelled() {
+            thread::sleep(Duration::from_millis(WATCH_INTERVAL_MS));
+
+            let changed_paths = self.changed_paths();
+
+            if !changed_paths.is_empty() {
+                for path in changed_paths.iter() {
+                    println!("{} changed.", path.display());
+                }
+
+                return true;
+            }
+        }
+
+        false
+    }
+}
+
+pub fn config_source_files(
+    config_path: &Path,
+    parameters_path: Option<&Path>,
+    env: Option<&str>,
+) -> Vec<PathBuf> {
+    let mut paths = match preprocess(config_path, &Definitions::default()) {
+        Ok(source) => source.files,
+        Err(_) => vec![config_path.to_path_buf()],
+    };
+
+    paths.push(aliases_path());
+    paths.extend(parameters_path.map(Path::to_path_buf));
+    paths.extend(env.map(|env| overlay_path(config_path, env)));
+
+    paths
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}